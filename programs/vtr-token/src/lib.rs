@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer, MintTo};
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::system_program;
+use anchor_spl::token::spl_token::instruction::AuthorityType;
+use anchor_spl::token::{self, Burn, CloseAccount, Mint, SetAuthority, Token, TokenAccount, Transfer, MintTo};
 use anchor_spl::associated_token::AssociatedToken;
 
 declare_id!("2jYy4kkMB6hTj9uZCDhCPqUyWaMBRRmZDTjW8rET9kD6");
@@ -11,367 +14,2679 @@ pub mod vtr_token {
     pub fn initialize_token(
         ctx: Context<InitializeToken>,
         total_supply: u64,
+        category_caps: [u64; ALLOCATION_TYPE_COUNT],
     ) -> Result<()> {
+        // Category budgets can't promise more than the total supply
+        let total_caps = category_caps
+            .iter()
+            .try_fold(0u64, |total, cap| total.checked_add(*cap))
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(total_caps <= total_supply, ErrorCode::InvalidAllocationBudget);
+
+        let allocation_budget = &mut ctx.accounts.allocation_budget;
+        allocation_budget.mint = ctx.accounts.mint.key();
+        allocation_budget.caps = category_caps;
+        allocation_budget.allocated = [0; ALLOCATION_TYPE_COUNT];
+        allocation_budget.minted = [0; ALLOCATION_TYPE_COUNT];
+        allocation_budget.bump = ctx.bumps.allocation_budget;
+
         let token_data = &mut ctx.accounts.token_data;
         token_data.authority = ctx.accounts.authority.key();
         token_data.mint = ctx.accounts.mint.key();
         token_data.total_supply = total_supply;
         token_data.circulating_supply = 0;
         token_data.burned_supply = 0;
+        token_data.allocation_count = 0;
+        token_data.tge_timestamp = 0;
+        token_data.locked_supply = 0;
+        token_data.allocated_supply = 0;
+        token_data.crank_tip_amount = 0;
+        token_data.crank_tip_budget = 0;
+        token_data.crank_interval = DEFAULT_CRANK_INTERVAL;
         token_data.bump = ctx.bumps.token_data;
         
         Ok(())
     }
 
+    pub fn set_tge_timestamp(ctx: Context<SetTgeTimestamp>, tge_timestamp: i64) -> Result<()> {
+        let token_data = &mut ctx.accounts.token_data;
+        require!(token_data.tge_timestamp == 0, ErrorCode::TgeAlreadySet);
+        require!(tge_timestamp > 0, ErrorCode::InvalidTgeTimestamp);
+
+        // Every allocation vests from this single timestamp
+        token_data.tge_timestamp = tge_timestamp;
+
+        Ok(())
+    }
+
+    pub fn initialize_vesting_template(
+        ctx: Context<InitializeVestingTemplate>,
+        allocation_type: AllocationType,
+        schedule: VestingSchedule,
+    ) -> Result<()> {
+        validate_vesting_schedule(&schedule)?;
+
+        let vesting_template = &mut ctx.accounts.vesting_template;
+        vesting_template.mint = ctx.accounts.mint.key();
+        vesting_template.allocation_type = allocation_type;
+        vesting_template.schedule = schedule;
+        vesting_template.beneficiary_change_requires_authority = false;
+        vesting_template.position_tokens_allowed = true;
+        vesting_template.bump = ctx.bumps.vesting_template;
+
+        Ok(())
+    }
+
+    pub fn update_vesting_template(
+        ctx: Context<UpdateVestingTemplate>,
+        schedule: VestingSchedule,
+    ) -> Result<()> {
+        validate_vesting_schedule(&schedule)?;
+
+        // Existing allocations keep the schedule they were created with
+        ctx.accounts.vesting_template.schedule = schedule;
+
+        Ok(())
+    }
+
+    pub fn set_beneficiary_change_policy(
+        ctx: Context<UpdateVestingTemplate>,
+        requires_authority: bool,
+    ) -> Result<()> {
+        // Read at proposal time, so it applies to existing allocations of the category too
+        ctx.accounts.vesting_template.beneficiary_change_requires_authority = requires_authority;
+
+        Ok(())
+    }
+
+    pub fn set_position_token_policy(ctx: Context<UpdateVestingTemplate>, allowed: bool) -> Result<()> {
+        // Only gates new tokenizations; existing position tokens stay valid
+        ctx.accounts.vesting_template.position_tokens_allowed = allowed;
+
+        Ok(())
+    }
+
     pub fn mint_tokens(
         ctx: Context<MintTokens>,
         amount: u64,
         allocation_type: AllocationType,
+        revocable: bool,
+        milestones: Vec<MilestoneTerms>,
     ) -> Result<()> {
-        // Validate minting doesn't exceed total supply
-        require!(
-            ctx.accounts.token_data.circulating_supply + amount <= ctx.accounts.token_data.total_supply,
-            ErrorCode::ExceedsTotalSupply
-        );
+        let request = AllocationRequest {
+            recipient: ctx.accounts.recipient.key(),
+            amount,
+            allocation_type,
+            revocable,
+            milestones,
+        };
+        let allocation_key = ctx.accounts.allocation.key();
+
+        let tge_amount = create_allocation_record(
+            &mut ctx.accounts.token_data,
+            &mut ctx.accounts.allocation_budget,
+            &mut ctx.accounts.allocation_registry,
+            &mut ctx.accounts.allocation,
+            allocation_key,
+            &request,
+            &ctx.accounts.vesting_template.schedule,
+        )?;
+        ctx.accounts.allocation_registry.bump = ctx.bumps.allocation_registry;
 
-        // Calculate TGE unlock amount
-        let tge_unlock_percentage = get_tge_unlock(&allocation_type);
-        let tge_amount = (amount * tge_unlock_percentage as u64) / 10000;
-        
-        // Create allocation record
         let allocation = &mut ctx.accounts.allocation;
-        allocation.recipient = ctx.accounts.recipient.key();
-        allocation.amount = amount;
-        allocation.allocation_type = allocation_type.clone();
-        allocation.vesting_start = Clock::get()?.unix_timestamp;
-        allocation.cliff_duration = get_cliff_duration(&allocation_type);
-        allocation.vesting_duration = get_vesting_duration(&allocation_type);
-        allocation.tge_unlock_percentage = tge_unlock_percentage;
-        allocation.claimed_amount = 0;
+        allocation.escrowed = ctx.accounts.allocation_vault.is_some();
+        allocation.payer = ctx.accounts.authority.key();
         allocation.bump = ctx.bumps.allocation;
 
         if tge_amount > 0 {
-            // Store values needed for seeds before borrowing
-            let mint_key = ctx.accounts.mint.key();
-            let token_data_bump = ctx.accounts.token_data.bump;
-            
-            let seeds = &[
-                b"token_data".as_ref(),
-                mint_key.as_ref(),
-                &[token_data_bump],
-            ];
-            let signer = &[&seeds[..]];
-            
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.mint.to_account_info(),
-                to: ctx.accounts.recipient_token_account.to_account_info(),
-                authority: ctx.accounts.token_data.to_account_info(),
-            };
-            
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-            
-            token::mint_to(cpi_ctx, tge_amount)?;
-            
-            allocation.claimed_amount = tge_amount;
+            mint_with_token_data(
+                ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.mint,
+                &ctx.accounts.token_data,
+                ctx.accounts.recipient_token_account.to_account_info(),
+                tge_amount,
+            )?;
+            record_tge_claim(
+                &mut ctx.accounts.allocation,
+                allocation_key,
+                ctx.accounts.recipient_token_account.key(),
+                tge_amount,
+            )?;
+        }
+
+        // In escrow mode everything not paid at TGE is minted up front into the allocation's vault
+        let mut escrowed_amount = 0;
+        if let Some(allocation_vault) = &ctx.accounts.allocation_vault {
+            escrowed_amount = amount - tge_amount;
+            if escrowed_amount > 0 {
+                // Pick up the TGE mint so the supply cap check sees it
+                ctx.accounts.mint.reload()?;
+                mint_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    &ctx.accounts.mint,
+                    &ctx.accounts.token_data,
+                    allocation_vault.to_account_info(),
+                    escrowed_amount,
+                )?;
+            }
         }
 
         // Update token data (mutable borrow at the end)
         let token_data = &mut ctx.accounts.token_data;
         token_data.circulating_supply += tge_amount;
+        token_data.locked_supply += escrowed_amount;
+        ctx.accounts.allocation_budget.minted[allocation_type as usize] += tge_amount + escrowed_amount;
         
         Ok(())
     }
 
-    pub fn claim_vested_tokens(ctx: Context<ClaimVestedTokens>) -> Result<()> {
-        let allocation = &mut ctx.accounts.allocation;
-        let current_time = Clock::get()?.unix_timestamp;
-        
-        let claimable_amount = calculate_claimable_amount(allocation, current_time)?;
-        
-        require!(claimable_amount > 0, ErrorCode::NoTokensToClaim);
+    pub fn batch_mint_allocations<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchMintAllocations<'info>>,
+        requests: Vec<AllocationRequest>,
+    ) -> Result<()> {
+        require!(!requests.is_empty(), ErrorCode::InvalidBatchAccounts);
+        require!(
+            ctx.remaining_accounts.len() == requests.len() * BATCH_ACCOUNTS_PER_ALLOCATION,
+            ErrorCode::InvalidBatchAccounts
+        );
 
-        // Store values needed for seeds before any CPI calls
         let mint_key = ctx.accounts.mint.key();
-        let token_data_bump = ctx.accounts.token_data.bump;
-        
-        let seeds = &[
-            b"token_data".as_ref(),
-            mint_key.as_ref(),
-            &[token_data_bump],
-        ];
-        let signer = &[&seeds[..]];
-        
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.mint.to_account_info(),
-            to: ctx.accounts.recipient_token_account.to_account_info(),
-            authority: ctx.accounts.token_data.to_account_info(),
-        };
-        
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        
-        token::mint_to(cpi_ctx, claimable_amount)?;
-        
-        allocation.claimed_amount += claimable_amount;
-        
-        // Update token data (mutable borrow at the end)
-        let token_data = &mut ctx.accounts.token_data;
-        token_data.circulating_supply += claimable_amount;
-        
-        Ok(())
-    }
 
-    pub fn burn_tokens(ctx: Context<BurnTokens>, amount: u64) -> Result<()> {
-        // Transfer tokens to burn account
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.from_token_account.to_account_info(),
-            to: ctx.accounts.burn_vault.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        
-        token::transfer(cpi_ctx, amount)?;
-        
-        let token_data = &mut ctx.accounts.token_data;
-        token_data.burned_supply += amount;
-        token_data.circulating_supply -= amount;
-        
+        // Each request brings its recipient, recipient token account, registry, allocation and template
+        let batch_accounts = ctx.remaining_accounts.chunks(BATCH_ACCOUNTS_PER_ALLOCATION);
+        for (request, accounts) in requests.iter().zip(batch_accounts) {
+            let [recipient, recipient_token_account, allocation_registry_info, allocation_info, vesting_template_info] =
+                accounts
+            else {
+                return err!(ErrorCode::InvalidBatchAccounts);
+            };
+            require_keys_eq!(*recipient.key, request.recipient, ErrorCode::InvalidBatchAccounts);
+
+            let vesting_template = Account::<VestingTemplate>::try_from(vesting_template_info)?;
+            require!(
+                vesting_template.mint == mint_key
+                    && vesting_template.allocation_type == request.allocation_type,
+                ErrorCode::InvalidBatchAccounts
+            );
+
+            anchor_spl::associated_token::create_idempotent(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.authority.to_account_info(),
+                    associated_token: recipient_token_account.clone(),
+                    authority: recipient.clone(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ))?;
+
+            // The registry only needs creating for a recipient's first allocation
+            let (registry_key, registry_bump) = Pubkey::find_program_address(
+                &[b"allocation_registry", mint_key.as_ref(), recipient.key.as_ref()],
+                &crate::ID,
+            );
+            require_keys_eq!(*allocation_registry_info.key, registry_key, ErrorCode::InvalidBatchAccounts);
+
+            let mut allocation_registry = if allocation_registry_info.data_is_empty() {
+                create_program_account(
+                    ctx.accounts.authority.to_account_info(),
+                    allocation_registry_info.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                    8 + AllocationRegistry::LEN,
+                    &[b"allocation_registry", mint_key.as_ref(), recipient.key.as_ref(), &[registry_bump]],
+                )?;
+                let mut allocation_registry =
+                    Account::<AllocationRegistry>::try_from_unchecked(allocation_registry_info)?;
+                allocation_registry.bump = registry_bump;
+                allocation_registry
+            } else {
+                Account::<AllocationRegistry>::try_from(allocation_registry_info)?
+            };
+
+            let allocation_id = ctx.accounts.token_data.allocation_count.to_le_bytes();
+            let (allocation_key, allocation_bump) = Pubkey::find_program_address(
+                &[b"allocation", mint_key.as_ref(), &allocation_id],
+                &crate::ID,
+            );
+            require_keys_eq!(*allocation_info.key, allocation_key, ErrorCode::InvalidBatchAccounts);
+
+            create_program_account(
+                ctx.accounts.authority.to_account_info(),
+                allocation_info.clone(),
+                ctx.accounts.system_program.to_account_info(),
+                8 + TokenAllocation::LEN,
+                &[b"allocation", mint_key.as_ref(), &allocation_id, &[allocation_bump]],
+            )?;
+            let mut allocation = Account::<TokenAllocation>::try_from_unchecked(allocation_info)?;
+
+            let tge_amount = create_allocation_record(
+                &mut ctx.accounts.token_data,
+                &mut ctx.accounts.allocation_budget,
+                &mut allocation_registry,
+                &mut allocation,
+                allocation_key,
+                request,
+                &vesting_template.schedule,
+            )?;
+            allocation.payer = ctx.accounts.authority.key();
+            allocation.bump = allocation_bump;
+
+            if tge_amount > 0 {
+                // Pick up earlier mints in the batch so the supply cap check sees them
+                ctx.accounts.mint.reload()?;
+                mint_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    &ctx.accounts.mint,
+                    &ctx.accounts.token_data,
+                    recipient_token_account.clone(),
+                    tge_amount,
+                )?;
+                record_tge_claim(&mut allocation, allocation_key, recipient_token_account.key(), tge_amount)?;
+
+                ctx.accounts.token_data.circulating_supply += tge_amount;
+                ctx.accounts.allocation_budget.minted[request.allocation_type as usize] += tge_amount;
+            }
+
+            allocation_registry.exit(&crate::ID)?;
+            allocation.exit(&crate::ID)?;
+        }
+
         Ok(())
     }
 
-    pub fn initialize_staking(
-        ctx: Context<InitializeStaking>,
-        apy_percentage: u16,
-        min_stake_duration: i64,
+    pub fn create_merkle_distribution(
+        ctx: Context<CreateMerkleDistribution>,
+        merkle_root: [u8; 32],
+        allocation_type: AllocationType,
+        total_amount: u64,
     ) -> Result<()> {
-        let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.authority = ctx.accounts.authority.key();
-        staking_pool.apy_percentage = apy_percentage;
-        staking_pool.min_stake_duration = min_stake_duration;
-        staking_pool.total_staked = 0;
-        staking_pool.bump = ctx.bumps.staking_pool;
-        
+        require!(total_amount > 0, ErrorCode::InvalidAmount);
+
+        // Reserve the whole distribution up front, like one large allocation
+        let token_data = &mut ctx.accounts.token_data;
+        token_data.allocated_supply = reserve_supply(token_data, total_amount)?;
+        ctx.accounts.allocation_budget.reserve(allocation_type, total_amount)?;
+
+        let merkle_distribution = &mut ctx.accounts.merkle_distribution;
+        merkle_distribution.mint = ctx.accounts.mint.key();
+        merkle_distribution.merkle_root = merkle_root;
+        merkle_distribution.allocation_type = allocation_type;
+        merkle_distribution.schedule = ctx.accounts.vesting_template.schedule.clone();
+        merkle_distribution.total_amount = total_amount;
+        merkle_distribution.claimed_amount = 0;
+        merkle_distribution.bump = ctx.bumps.merkle_distribution;
+
         Ok(())
     }
 
-    pub fn stake_tokens(
-        ctx: Context<StakeTokens>,
+    pub fn claim_merkle_allocation(
+        ctx: Context<ClaimMerkleAllocation>,
         amount: u64,
-        duration: i64,
+        proof: Vec<[u8; 32]>,
     ) -> Result<()> {
-        require!(amount > 0, ErrorCode::InvalidAmount);
+        let distribution_key = ctx.accounts.merkle_distribution.key();
+        let merkle_distribution = &ctx.accounts.merkle_distribution;
+        let claim_status = &mut ctx.accounts.claim_status;
+
+        // The proof is only needed on the first claim, which pins the recipient's amount
+        if claim_status.distribution == Pubkey::default() {
+            let leaf = hashv(&[
+                &[MERKLE_LEAF_PREFIX],
+                ctx.accounts.recipient.key().as_ref(),
+                &amount.to_le_bytes(),
+                &[merkle_distribution.allocation_type as u8],
+            ]);
+            require!(
+                verify_merkle_proof(&proof, merkle_distribution.merkle_root, leaf.to_bytes()),
+                ErrorCode::InvalidMerkleProof
+            );
+
+            claim_status.distribution = distribution_key;
+            claim_status.recipient = ctx.accounts.recipient.key();
+            claim_status.amount = amount;
+            claim_status.claimed_amount = 0;
+            claim_status.bump = ctx.bumps.claim_status;
+        }
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let vested_amount = calculate_schedule_vested_amount(
+            claim_status.amount,
+            &merkle_distribution.schedule,
+            ctx.accounts.token_data.tge_timestamp,
+            current_time,
+        )?;
+        let claimable_amount = vested_amount.saturating_sub(claim_status.claimed_amount);
+        require!(claimable_amount > 0, ErrorCode::NoTokensToClaim);
+
+        // A bad root can't pay out more than the distribution reserved
+        let distributed_amount = merkle_distribution
+            .claimed_amount
+            .checked_add(claimable_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
         require!(
-            duration >= ctx.accounts.staking_pool.min_stake_duration,
-            ErrorCode::InsufficientStakeDuration
+            distributed_amount <= merkle_distribution.total_amount,
+            ErrorCode::MerkleDistributionExhausted
         );
 
-        // Transfer tokens to staking vault
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.staking_vault.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        
-        token::transfer(cpi_ctx, amount)?;
+        mint_with_token_data(
+            ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.token_data,
+            ctx.accounts.recipient_token_account.to_account_info(),
+            claimable_amount,
+        )?;
 
-        // Create stake account
-        let stake_account = &mut ctx.accounts.stake_account;
-        stake_account.user = ctx.accounts.user.key();
-        stake_account.amount = amount;
-        stake_account.stake_time = Clock::get()?.unix_timestamp;
-        stake_account.unlock_time = stake_account.stake_time + duration;
-        stake_account.claimed_rewards = 0;
-        stake_account.bump = ctx.bumps.stake_account;
+        claim_status.claimed_amount += claimable_amount;
+        let allocation_type = merkle_distribution.allocation_type;
+        ctx.accounts.merkle_distribution.claimed_amount = distributed_amount;
+        ctx.accounts.allocation_budget.minted[allocation_type as usize] += claimable_amount;
 
-        // Update staking pool
-        let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.total_staked += amount;
+        // Update token data (mutable borrow at the end)
+        ctx.accounts.token_data.circulating_supply += claimable_amount;
+
+        Ok(())
+    }
+
+    pub fn claim_vested_tokens(ctx: Context<ClaimVestedTokens>) -> Result<()> {
+        verify_beneficiary(
+            &ctx.accounts.allocation,
+            ctx.accounts.recipient.key(),
+            ctx.accounts.position_token_account.as_ref(),
+        )?;
+
+        release_vested_tokens(
+            ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.token_data,
+            &mut ctx.accounts.allocation_budget,
+            &mut ctx.accounts.allocation,
+            ctx.accounts.allocation_vault.as_ref(),
+            ctx.accounts.recipient_token_account.to_account_info(),
+        )?;
         
         Ok(())
     }
 
-    pub fn unstake_tokens(ctx: Context<UnstakeTokens>) -> Result<()> {
-        let stake_account = &ctx.accounts.stake_account;
+    pub fn preview_allocation(ctx: Context<PreviewAllocation>) -> Result<AllocationPreview> {
+        let allocation = &ctx.accounts.allocation;
+        let tge_timestamp = ctx.accounts.token_data.tge_timestamp;
         let current_time = Clock::get()?.unix_timestamp;
-        
+
+        let vested_amount = calculate_vested_amount(allocation, tge_timestamp, current_time)?;
+        let (mut next_unlock_timestamp, fully_vested_timestamp) =
+            calculate_unlock_timestamps(allocation, tge_timestamp, current_time)?;
+        // Acceleration can vest everything ahead of the schedule
+        if vested_amount >= allocation.amount {
+            next_unlock_timestamp = 0;
+        }
+
+        // Returned through `set_return_data`, so simulations and CPI callers can read it
+        Ok(AllocationPreview {
+            vested_amount,
+            claimed_amount: allocation.claimed_amount,
+            claimable_amount: vested_amount.saturating_sub(allocation.claimed_amount),
+            next_unlock_timestamp,
+            fully_vested_timestamp,
+        })
+    }
+
+    pub fn configure_crank_tip(
+        ctx: Context<ConfigureCrankTip>,
+        tip_amount: u64,
+        tip_budget: u64,
+        crank_interval: i64,
+    ) -> Result<()> {
+        // Tipped cranks must be throttled, or bots could drain the budget one dust release at a time
         require!(
-            current_time >= stake_account.unlock_time,
-            ErrorCode::StakingPeriodNotEnded
+            crank_interval >= 0 && (tip_amount == 0 || crank_interval > 0),
+            ErrorCode::InvalidCrankInterval
         );
+        let token_data = &mut ctx.accounts.token_data;
 
-        // Calculate pending rewards
-        let pending_rewards = calculate_staking_rewards(
-            stake_account.amount,
-            ctx.accounts.staking_pool.apy_percentage,
-            stake_account.stake_time,
-            current_time,
-        );
+        // The tip budget is reserved like an allocation; replacing it releases the old remainder
+        token_data.allocated_supply -= token_data.crank_tip_budget;
+        token_data.allocated_supply = reserve_supply(token_data, tip_budget)?;
+        token_data.crank_tip_amount = tip_amount;
+        token_data.crank_tip_budget = tip_budget;
+        token_data.crank_interval = crank_interval;
 
-        // Store values needed for seeds before any CPI calls
-        let authority_key = ctx.accounts.staking_pool.authority;
-        let staking_pool_bump = ctx.accounts.staking_pool.bump;
-        let stake_amount = stake_account.amount;
+        Ok(())
+    }
+
+    pub fn set_crank_opt_out(ctx: Context<SetCrankOptOut>, opt_out: bool) -> Result<()> {
+        ctx.accounts.allocation.crank_opt_out = opt_out;
+        
+        Ok(())
+    }
+
+    pub fn propose_beneficiary_change(
+        ctx: Context<ProposeBeneficiaryChange>,
+        new_recipient: Pubkey,
+    ) -> Result<()> {
+        if ctx.accounts.vesting_template.beneficiary_change_requires_authority {
+            let authority = ctx
+                .accounts
+                .authority
+                .as_ref()
+                .ok_or(ErrorCode::AuthorityCosignRequired)?;
+            require_keys_eq!(
+                authority.key(),
+                ctx.accounts.token_data.authority,
+                ErrorCode::AuthorityCosignRequired
+            );
+        }
+
+        require_keys_neq!(new_recipient, ctx.accounts.allocation.recipient, ErrorCode::SameBeneficiary);
+
+        // Proposing the default key cancels a pending change
+        ctx.accounts.allocation.pending_recipient = new_recipient;
+
+        Ok(())
+    }
+
+    pub fn accept_beneficiary_change(ctx: Context<AcceptBeneficiaryChange>) -> Result<()> {
+        let allocation_key = ctx.accounts.allocation.key();
+        reassign_beneficiary(
+            &mut ctx.accounts.allocation,
+            allocation_key,
+            &mut ctx.accounts.current_registry,
+            &mut ctx.accounts.new_registry,
+            ctx.accounts.new_recipient.key(),
+        )?;
+        ctx.accounts.new_registry.bump = ctx.bumps.new_registry;
+
+        Ok(())
+    }
+
+    pub fn tokenize_allocation(ctx: Context<TokenizeAllocation>) -> Result<()> {
+        require!(
+            ctx.accounts.vesting_template.position_tokens_allowed,
+            ErrorCode::PositionTokensDisabled
+        );
+        let allocation = &ctx.accounts.allocation;
+        require!(allocation.position_mint == Pubkey::default(), ErrorCode::AllocationTokenized);
+        require!(!allocation.revoked, ErrorCode::AllocationAlreadyRevoked);
+        require!(!allocation.frozen, ErrorCode::AllocationFrozen);
 
-        // Transfer staked tokens back
         let seeds = &[
-            b"staking_pool".as_ref(),
-            authority_key.as_ref(),
-            &[staking_pool_bump],
+            b"token_data".as_ref(),
+            ctx.accounts.token_data.mint.as_ref(),
+            &[ctx.accounts.token_data.bump],
         ];
         let signer = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.staking_vault.to_account_info(),
-            to: ctx.accounts.user_token_account.to_account_info(),
-            authority: ctx.accounts.staking_pool.to_account_info(),
+        // Mint the single position token, then drop the mint authority so it stays 1-of-1
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.position_mint.to_account_info(),
+            to: ctx.accounts.position_token_account.to_account_info(),
+            authority: ctx.accounts.token_data.to_account_info(),
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-        
-        token::transfer(cpi_ctx, stake_amount)?;
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::mint_to(cpi_ctx, 1)?;
 
-        // Mint rewards if any
-        if pending_rewards > 0 {
-            let mint_key = ctx.accounts.mint.key();
-            let token_data_bump = ctx.accounts.token_data.bump;
-            
-            let seeds = &[
-                b"token_data".as_ref(),
-                mint_key.as_ref(),
-                &[token_data_bump],
-            ];
-            let signer = &[&seeds[..]];
-            
-            let cpi_accounts = MintTo {
-                mint: ctx.accounts.mint.to_account_info(),
-                to: ctx.accounts.user_token_account.to_account_info(),
-                authority: ctx.accounts.token_data.to_account_info(),
-            };
-            
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-            
-            token::mint_to(cpi_ctx, pending_rewards)?;
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.token_data.to_account_info(),
+            account_or_mint: ctx.accounts.position_mint.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        token::set_authority(cpi_ctx, AuthorityType::MintTokens, None)?;
+
+        let allocation = &mut ctx.accounts.allocation;
+        allocation.position_mint = ctx.accounts.position_mint.key();
+        allocation.pending_recipient = Pubkey::default();
+
+        Ok(())
+    }
+
+    pub fn configure_recovery(
+        ctx: Context<ConfigureRecovery>,
+        guardians: Vec<Pubkey>,
+        threshold: u8,
+        recovery_delay: i64,
+    ) -> Result<()> {
+        require!(
+            !guardians.is_empty() && guardians.len() <= MAX_RECOVERY_GUARDIANS,
+            ErrorCode::InvalidRecoveryConfig
+        );
+        require!(
+            threshold > 0
+                && threshold as usize <= guardians.len()
+                && recovery_delay >= MIN_RECOVERY_DELAY,
+            ErrorCode::InvalidRecoveryConfig
+        );
+        for (index, guardian) in guardians.iter().enumerate() {
+            require!(!guardians[..index].contains(guardian), ErrorCode::InvalidRecoveryConfig);
         }
 
-        // Update staking pool (mutable borrow at the end)
-        let staking_pool = &mut ctx.accounts.staking_pool;
-        staking_pool.total_staked -= stake_amount;
-        
+        // Reconfiguring also cancels any recovery in progress
+        let recovery = &mut ctx.accounts.recovery;
+        recovery.allocation = ctx.accounts.allocation.key();
+        recovery.recipient = ctx.accounts.allocation.recipient;
+        recovery.guardians = guardians;
+        recovery.threshold = threshold;
+        recovery.recovery_delay = recovery_delay;
+        recovery.clear_pending();
+        recovery.bump = ctx.bumps.recovery;
+
         Ok(())
     }
-}
 
-// Helper functions
-fn get_cliff_duration(allocation_type: &AllocationType) -> i64 {
-    match allocation_type {
-        AllocationType::TokenSale => 0,
-        AllocationType::TeamAdvisors => 12 * 30 * 24 * 3600, // 12 months
-        AllocationType::EcosystemGrowth => 0,
-        AllocationType::Liquidity => 0,
-        AllocationType::PlatformReserve => 6 * 30 * 24 * 3600, // 6 months
-        AllocationType::BuybackBurn => 0,
-        AllocationType::Marketing => 0,
+    pub fn approve_recovery(ctx: Context<ApproveRecovery>, new_recipient: Pubkey) -> Result<()> {
+        require_keys_neq!(new_recipient, Pubkey::default(), ErrorCode::InvalidRecoveryConfig);
+        require_keys_neq!(new_recipient, ctx.accounts.allocation.recipient, ErrorCode::SameBeneficiary);
+
+        let recovery = &mut ctx.accounts.recovery;
+        let guardian_index = recovery
+            .guardians
+            .iter()
+            .position(|guardian| *guardian == ctx.accounts.guardian.key())
+            .ok_or(ErrorCode::NotRecoveryGuardian)?;
+
+        // Once the waiting period runs, only the current key can stop it through `cancel_recovery`
+        require!(
+            recovery.threshold_met_at == 0 || recovery.pending_recipient == new_recipient,
+            ErrorCode::RecoveryInProgress
+        );
+
+        // Each guardian backs one key, so changing their mind never touches the others' approvals
+        recovery.approvals[guardian_index] = new_recipient;
+        let approval_count = recovery
+            .approvals
+            .iter()
+            .filter(|approved| **approved == new_recipient)
+            .count();
+
+        // The waiting period starts once the threshold is first met
+        if approval_count >= recovery.threshold as usize && recovery.threshold_met_at == 0 {
+            recovery.pending_recipient = new_recipient;
+            recovery.threshold_met_at = Clock::get()?.unix_timestamp;
+        }
+
+        Ok(())
     }
-}
 
-fn get_vesting_duration(allocation_type: &AllocationType) -> i64 {
-    match allocation_type {
-        AllocationType::TokenSale => 12 * 30 * 24 * 3600, // 12 months
-        AllocationType::TeamAdvisors => 36 * 30 * 24 * 3600, // 36 months
-        AllocationType::EcosystemGrowth => 48 * 30 * 24 * 3600, // 48 months
-        AllocationType::Liquidity => 6 * 30 * 24 * 3600, // 6 months
-        AllocationType::PlatformReserve => 60 * 30 * 24 * 3600, // 60 months
-        AllocationType::BuybackBurn => 24 * 30 * 24 * 3600, // 24 months
-        AllocationType::Marketing => 18 * 30 * 24 * 3600, // 18 months
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        ctx.accounts.recovery.clear_pending();
+
+        Ok(())
     }
-}
 
-fn get_tge_unlock(allocation_type: &AllocationType) -> u16 {
-    match allocation_type {
-        AllocationType::TokenSale => 1000, // 10%
-        AllocationType::TeamAdvisors => 0, // 0%
-        AllocationType::EcosystemGrowth => 1500, // 15%
-        AllocationType::Liquidity => 5000, // 50%
-        AllocationType::PlatformReserve => 500, // 5%
-        AllocationType::BuybackBurn => 0, // 0%
-        AllocationType::Marketing => 2000, // 20%
+    pub fn execute_recovery(ctx: Context<ExecuteRecovery>) -> Result<()> {
+        let recovery = &ctx.accounts.recovery;
+        require!(recovery.threshold_met_at != 0, ErrorCode::RecoveryNotReady);
+        let ready_at = recovery
+            .threshold_met_at
+            .checked_add(recovery.recovery_delay)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(Clock::get()?.unix_timestamp >= ready_at, ErrorCode::RecoveryNotReady);
+
+        let allocation_key = ctx.accounts.allocation.key();
+        reassign_beneficiary(
+            &mut ctx.accounts.allocation,
+            allocation_key,
+            &mut ctx.accounts.current_registry,
+            &mut ctx.accounts.new_registry,
+            ctx.accounts.new_recipient.key(),
+        )?;
+        ctx.accounts.new_registry.bump = ctx.bumps.new_registry;
+
+        // Guardians stay configured for the recovered key
+        let recovery = &mut ctx.accounts.recovery;
+        recovery.recipient = ctx.accounts.allocation.recipient;
+        recovery.clear_pending();
+
+        Ok(())
     }
+
+    pub fn crank_claim(ctx: Context<CrankClaim>) -> Result<()> {
+        require!(!ctx.accounts.allocation.crank_opt_out, ErrorCode::CrankOptedOut);
+
+        // Throttle cranks per allocation so tips and claim history can't be farmed with dust releases
+        let current_time = Clock::get()?.unix_timestamp;
+        let next_crank_at = ctx
+            .accounts
+            .allocation
+            .last_cranked_at
+            .checked_add(ctx.accounts.token_data.crank_interval)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(current_time >= next_crank_at, ErrorCode::CrankCooldown);
+        ctx.accounts.allocation.last_cranked_at = current_time;
+
+        release_vested_tokens(
+            ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.token_data,
+            &mut ctx.accounts.allocation_budget,
+            &mut ctx.accounts.allocation,
+            ctx.accounts.allocation_vault.as_ref(),
+            ctx.accounts.recipient_token_account.to_account_info(),
+        )?;
+
+        // Tip the cranker while the configured budget lasts
+        let tip_amount = ctx.accounts.token_data.crank_tip_amount;
+        if let Some(cranker_token_account) = &ctx.accounts.cranker_token_account {
+            if tip_amount > 0 && ctx.accounts.token_data.crank_tip_budget >= tip_amount {
+                // Pick up the claim mint so the supply cap check sees it
+                ctx.accounts.mint.reload()?;
+                mint_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    &ctx.accounts.mint,
+                    &ctx.accounts.token_data,
+                    cranker_token_account.to_account_info(),
+                    tip_amount,
+                )?;
+
+                let token_data = &mut ctx.accounts.token_data;
+                token_data.crank_tip_budget -= tip_amount;
+                token_data.circulating_supply += tip_amount;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn claim_all_vested_tokens<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimAllVestedTokens<'info>>,
+    ) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        let recipient_key = ctx.accounts.recipient.key();
+        let mut minted_amount: u64 = 0;
+        let mut unlocked_amount: u64 = 0;
+
+        // Remaining accounts are registered allocations, each escrowed one followed by its vault
+        let mut remaining_accounts = ctx.remaining_accounts.iter();
+        while let Some(allocation_info) = remaining_accounts.next() {
+            require!(
+                ctx.accounts.allocation_registry.allocations.contains(allocation_info.key),
+                ErrorCode::AllocationNotRegistered
+            );
+            require!(allocation_info.is_writable, ErrorCode::AllocationNotRegistered);
+
+            let mut allocation = Account::<TokenAllocation>::try_from(allocation_info)?;
+            require_keys_eq!(allocation.recipient, recipient_key, ErrorCode::AllocationNotRegistered);
+            require!(!allocation.frozen, ErrorCode::AllocationFrozen);
+            require!(allocation.position_mint == Pubkey::default(), ErrorCode::AllocationTokenized);
+
+            let allocation_vault = if allocation.escrowed {
+                let allocation_vault = remaining_accounts
+                    .next()
+                    .ok_or(ErrorCode::InvalidAllocationVault)?;
+                let (expected_vault, _) = Pubkey::find_program_address(
+                    &[b"allocation_vault", allocation_info.key.as_ref()],
+                    &crate::ID,
+                );
+                require_keys_eq!(*allocation_vault.key, expected_vault, ErrorCode::InvalidAllocationVault);
+                Some(allocation_vault)
+            } else {
+                None
+            };
+
+            let claimable_amount = calculate_claimable_amount(
+                &allocation,
+                ctx.accounts.token_data.tge_timestamp,
+                current_time,
+            )?;
+            if claimable_amount == 0 {
+                continue;
+            }
+
+            if let Some(allocation_vault) = allocation_vault {
+                transfer_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    &ctx.accounts.token_data,
+                    allocation_vault.clone(),
+                    ctx.accounts.recipient_token_account.to_account_info(),
+                    claimable_amount,
+                )?;
+                unlocked_amount += claimable_amount;
+            } else {
+                minted_amount += claimable_amount;
+                ctx.accounts.allocation_budget.minted[allocation.allocation_type as usize] += claimable_amount;
+            }
+
+            allocation.claimed_amount += claimable_amount;
+            allocation.record_claim(ClaimRecord {
+                timestamp: current_time,
+                amount: claimable_amount,
+                destination: ctx.accounts.recipient_token_account.key(),
+            });
+            allocation.exit(&crate::ID)?;
+
+            emit!(TokensClaimed {
+                allocation: allocation.key(),
+                recipient: recipient_key,
+                destination: ctx.accounts.recipient_token_account.key(),
+                amount: claimable_amount,
+                timestamp: current_time,
+            });
+        }
+
+        require!(minted_amount + unlocked_amount > 0, ErrorCode::NoTokensToClaim);
+
+        // Mint-on-claim allocations are paid in a single CPI
+        if minted_amount > 0 {
+            mint_with_token_data(
+                ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.mint,
+                &ctx.accounts.token_data,
+                ctx.accounts.recipient_token_account.to_account_info(),
+                minted_amount,
+            )?;
+        }
+        
+        // Update token data (mutable borrow at the end)
+        let token_data = &mut ctx.accounts.token_data;
+        token_data.locked_supply -= unlocked_amount;
+        token_data.circulating_supply += minted_amount + unlocked_amount;
+        
+        Ok(())
+    }
+
+    pub fn revoke_allocation(ctx: Context<RevokeAllocation>) -> Result<()> {
+        let allocation = &mut ctx.accounts.allocation;
+        require!(allocation.revocable, ErrorCode::AllocationNotRevocable);
+        require!(!allocation.revoked, ErrorCode::AllocationAlreadyRevoked);
+
+        let current_time = Clock::get()?.unix_timestamp;
+
+        // Freeze the grant at what has vested so far; anything already claimed stays vested
+        let vested_amount = std::cmp::max(
+            calculate_vested_amount(allocation, ctx.accounts.token_data.tge_timestamp, current_time)?,
+            allocation.claimed_amount,
+        );
+        let unvested_amount = allocation.amount - vested_amount;
+
+        allocation.amount = vested_amount;
+        allocation.revoked = true;
+        allocation.revoked_at = current_time;
+
+        if unvested_amount == 0 {
+            return Ok(());
+        }
+
+        let mut released_amount = 0;
+        if allocation.escrowed {
+            let allocation_vault = ctx
+                .accounts
+                .allocation_vault
+                .as_ref()
+                .ok_or(ErrorCode::InvalidAllocationVault)?;
+
+            // Escrowed remainder goes to the treasury or is burned back into the unallocated budget
+            if let Some(treasury_token_account) = &ctx.accounts.treasury_token_account {
+                transfer_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    &ctx.accounts.token_data,
+                    allocation_vault.to_account_info(),
+                    treasury_token_account.to_account_info(),
+                    unvested_amount,
+                )?;
+                released_amount = unvested_amount;
+            } else {
+                burn_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.mint.to_account_info(),
+                    &ctx.accounts.token_data,
+                    allocation_vault.to_account_info(),
+                    unvested_amount,
+                )?;
+            }
+        } else if let Some(treasury_token_account) = &ctx.accounts.treasury_token_account {
+            // Without a treasury the unvested remainder is never minted and returns to the unallocated budget
+            mint_with_token_data(
+                ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.mint,
+                &ctx.accounts.token_data,
+                treasury_token_account.to_account_info(),
+                unvested_amount,
+            )?;
+            released_amount = unvested_amount;
+        }
+
+        // Update token data (mutable borrow at the end)
+        let token_data = &mut ctx.accounts.token_data;
+        if allocation.escrowed {
+            token_data.locked_supply -= unvested_amount;
+        }
+        token_data.circulating_supply += released_amount;
+
+        // Anything not released to the treasury goes back to the unallocated budget
+        let returned_amount = unvested_amount - released_amount;
+        token_data.allocated_supply -= returned_amount;
+
+        let allocation_budget = &mut ctx.accounts.allocation_budget;
+        let category = allocation.allocation_type as usize;
+        allocation_budget.allocated[category] -= returned_amount;
+        if allocation.escrowed {
+            // Burned from the vault
+            allocation_budget.minted[category] -= returned_amount;
+        } else {
+            // Minted to the treasury
+            allocation_budget.minted[category] += released_amount;
+        }
+
+        Ok(())
+    }
+
+    pub fn close_allocation(ctx: Context<CloseAllocation>) -> Result<()> {
+        let allocation = &ctx.accounts.allocation;
+
+        // Fully claimed, or revoked with everything it kept already claimed
+        require!(
+            allocation.claimed_amount >= allocation.amount,
+            ErrorCode::AllocationNotSettled
+        );
+
+        if allocation.escrowed {
+            let allocation_vault = ctx
+                .accounts
+                .allocation_vault
+                .as_ref()
+                .ok_or(ErrorCode::InvalidAllocationVault)?;
+
+            // Anyone can send tokens to the vault; burn them so the close can't be blocked
+            let stray_amount = allocation_vault.amount;
+            if stray_amount > 0 {
+                burn_with_token_data(
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.mint.to_account_info(),
+                    &ctx.accounts.token_data,
+                    allocation_vault.to_account_info(),
+                    stray_amount,
+                )?;
+                let token_data = &mut ctx.accounts.token_data;
+                token_data.burned_supply += stray_amount;
+                token_data.circulating_supply = token_data.circulating_supply.saturating_sub(stray_amount);
+            }
+
+            close_with_token_data(
+                ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.token_data,
+                allocation_vault.to_account_info(),
+                ctx.accounts.payer.to_account_info(),
+            )?;
+        }
+
+        let allocation_key = allocation.key();
+        ctx.accounts
+            .allocation_registry
+            .allocations
+            .retain(|registered| *registered != allocation_key);
+
+        // The allocation account itself is closed to the payer by the `close` constraint
+        Ok(())
+    }
+
+    pub fn attest_milestone(ctx: Context<AttestMilestone>, milestone_index: u8) -> Result<()> {
+        let allocation = &mut ctx.accounts.allocation;
+        require!(!allocation.revoked, ErrorCode::AllocationAlreadyRevoked);
+
+        let milestone = allocation
+            .milestones
+            .get_mut(milestone_index as usize)
+            .ok_or(ErrorCode::InvalidMilestones)?;
+        require_keys_eq!(
+            milestone.attestor,
+            ctx.accounts.attestor.key(),
+            ErrorCode::InvalidMilestoneAttestor
+        );
+        require!(milestone.attested_at == 0, ErrorCode::MilestoneAlreadyAttested);
+
+        // The tranche becomes claimable on top of the time-based schedule
+        milestone.attested_at = Clock::get()?.unix_timestamp;
+
+        Ok(())
+    }
+
+    pub fn set_allocation_frozen(ctx: Context<SetAllocationFrozen>, frozen: bool) -> Result<()> {
+        // Vesting keeps accruing while frozen; only claims are held
+        ctx.accounts.allocation.frozen = frozen;
+
+        Ok(())
+    }
+
+    pub fn amend_allocation(
+        ctx: Context<AmendAllocation>,
+        amendment: AllocationAmendment,
+    ) -> Result<()> {
+        require!(!ctx.accounts.allocation.revoked, ErrorCode::AllocationAlreadyRevoked);
+
+        let current_time = Clock::get()?.unix_timestamp;
+        let tge_timestamp = ctx.accounts.token_data.tge_timestamp;
+
+        // Everything vested so far is kept through the amendment; the checkpoint covers the
+        // time-based part, since milestone tranches vest on attestation
+        let vested_amount = std::cmp::max(
+            calculate_vested_amount(&ctx.accounts.allocation, tge_timestamp, current_time)?,
+            ctx.accounts.allocation.claimed_amount,
+        );
+        let time_vested = calculate_time_vested_amount(&ctx.accounts.allocation, tge_timestamp, current_time)?;
+        let mut vested_base = time_vested;
+
+        match amendment {
+            AllocationAmendment::ChangeAmount { new_amount } => {
+                // Milestone tranches keep their amounts, so only the time-based part can be cut
+                let allocation = &ctx.accounts.allocation;
+                let (milestone_amount, _) = allocation.milestone_amounts()?;
+                let kept_amount = milestone_amount
+                    .checked_add(time_vested)
+                    .ok_or(ErrorCode::MathOverflow)?;
+                require!(
+                    new_amount >= vested_amount && new_amount >= kept_amount,
+                    ErrorCode::InvalidAmendment
+                );
+                let category = allocation.allocation_type as usize;
+
+                if new_amount > allocation.amount {
+                    let increase = new_amount - allocation.amount;
+                    let token_data = &mut ctx.accounts.token_data;
+                    token_data.allocated_supply = reserve_supply(token_data, increase)?;
+                    ctx.accounts.allocation_budget.reserve(allocation.allocation_type, increase)?;
+
+                    // Escrowed allocations keep their whole unvested amount in the vault
+                    if allocation.escrowed {
+                        let allocation_vault = ctx
+                            .accounts
+                            .allocation_vault
+                            .as_ref()
+                            .ok_or(ErrorCode::InvalidAllocationVault)?;
+                        mint_with_token_data(
+                            ctx.accounts.token_program.to_account_info(),
+                            &ctx.accounts.mint,
+                            &ctx.accounts.token_data,
+                            allocation_vault.to_account_info(),
+                            increase,
+                        )?;
+                        ctx.accounts.token_data.locked_supply += increase;
+                        ctx.accounts.allocation_budget.minted[category] += increase;
+                    }
+                } else {
+                    let decrease = allocation.amount - new_amount;
+                    if allocation.escrowed {
+                        let allocation_vault = ctx
+                            .accounts
+                            .allocation_vault
+                            .as_ref()
+                            .ok_or(ErrorCode::InvalidAllocationVault)?;
+                        burn_with_token_data(
+                            ctx.accounts.token_program.to_account_info(),
+                            ctx.accounts.mint.to_account_info(),
+                            &ctx.accounts.token_data,
+                            allocation_vault.to_account_info(),
+                            decrease,
+                        )?;
+                        ctx.accounts.token_data.locked_supply -= decrease;
+                        ctx.accounts.allocation_budget.minted[category] -= decrease;
+                    }
+
+                    // The cut goes back to the unallocated budget
+                    ctx.accounts.token_data.allocated_supply -= decrease;
+                    ctx.accounts.allocation_budget.allocated[category] -= decrease;
+                }
+
+                ctx.accounts.allocation.amount = new_amount;
+            }
+            AllocationAmendment::ExtendVesting { additional_duration } => {
+                let schedule = &mut ctx.accounts.allocation.schedule;
+                // Unlock tables carry absolute timestamps, so there is no duration to extend
+                require!(
+                    additional_duration > 0 && !matches!(schedule.curve, VestingCurve::Table { .. }),
+                    ErrorCode::InvalidAmendment
+                );
+                schedule.vesting_duration = schedule
+                    .vesting_duration
+                    .checked_add(additional_duration)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            AllocationAmendment::Accelerate { acceleration_bps } => {
+                require!(
+                    acceleration_bps > 0 && acceleration_bps <= 10000,
+                    ErrorCode::InvalidAmendment
+                );
+                // Vest a share of the remaining time-based amount immediately
+                let unvested_amount = ctx.accounts.allocation.time_vested_total()? - time_vested;
+                vested_base += mul_div(unvested_amount, acceleration_bps as u64, 10000)?;
+            }
+        }
+
+        // Checkpoint: the rest vests over what remains of the (possibly amended) schedule
+        let allocation = &mut ctx.accounts.allocation;
+        allocation.vested_base = vested_base;
+        allocation.progress_base = calculate_schedule_vested_amount(
+            VESTING_PROGRESS_PRECISION,
+            &allocation.schedule,
+            tge_timestamp,
+            current_time,
+        )?;
+        allocation.amendment_count += 1;
+        allocation.last_amended_at = current_time;
+
+        Ok(())
+    }
+
+    pub fn burn_tokens(ctx: Context<BurnTokens>, amount: u64) -> Result<()> {
+        // Transfer tokens to burn account
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.from_token_account.to_account_info(),
+            to: ctx.accounts.burn_vault.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        
+        token::transfer(cpi_ctx, amount)?;
+        
+        let token_data = &mut ctx.accounts.token_data;
+        token_data.burned_supply += amount;
+        token_data.circulating_supply -= amount;
+        
+        Ok(())
+    }
+
+    pub fn initialize_staking(
+        ctx: Context<InitializeStaking>,
+        apy_percentage: u16,
+        min_stake_duration: i64,
+    ) -> Result<()> {
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.authority = ctx.accounts.authority.key();
+        staking_pool.mint = ctx.accounts.mint.key();
+        staking_pool.apy_percentage = apy_percentage;
+        staking_pool.min_stake_duration = min_stake_duration;
+        staking_pool.total_staked = 0;
+        staking_pool.total_weight = 0;
+        staking_pool.tiers = Vec::new();
+        staking_pool.acc_reward_per_share = 0;
+        staking_pool.last_update_time = Clock::get()?.unix_timestamp;
+        staking_pool.bump = ctx.bumps.staking_pool;
+        
+        Ok(())
+    }
+
+    pub fn update_staking_apy(ctx: Context<UpdateStakingApy>, apy_percentage: u16) -> Result<()> {
+        // Settle the old rate up to now so the new one only applies going forward
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.update_rewards(Clock::get()?.unix_timestamp)?;
+        staking_pool.apy_percentage = apy_percentage;
+
+        Ok(())
+    }
+
+    pub fn set_staking_tiers(ctx: Context<UpdateStakingApy>, tiers: Vec<StakingTier>) -> Result<()> {
+        require!(tiers.len() <= MAX_STAKING_TIERS, ErrorCode::InvalidStakingTiers);
+        require!(
+            tiers.iter().all(|tier| tier.min_duration >= 0 && tier.boost_bps >= 10000),
+            ErrorCode::InvalidStakingTiers
+        );
+        // Ascending lock durations, and longer locks never boost less
+        require!(
+            tiers.windows(2).all(|pair| {
+                pair[1].min_duration > pair[0].min_duration && pair[1].boost_bps >= pair[0].boost_bps
+            }),
+            ErrorCode::InvalidStakingTiers
+        );
+
+        // Only new positions pick up the table; existing ones keep their boost
+        ctx.accounts.staking_pool.tiers = tiers;
+
+        Ok(())
+    }
+
+    pub fn stake_tokens(
+        ctx: Context<StakeTokens>,
+        amount: u64,
+        duration: i64,
+    ) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        require!(
+            duration >= ctx.accounts.staking_pool.min_stake_duration,
+            ErrorCode::InsufficientStakeDuration
+        );
+
+        // Transfer tokens to staking vault
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.staking_vault.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        
+        token::transfer(cpi_ctx, amount)?;
+
+        let current_time = Clock::get()?.unix_timestamp;
+        ctx.accounts.staking_pool.update_rewards(current_time)?;
+
+        // The tier is fixed at stake time
+        let boost_bps = ctx.accounts.staking_pool.boost_for_duration(duration);
+        let weight = calculate_stake_weight(amount, boost_bps)?;
+
+        // Create stake account; rewards accrued before it existed are excluded through its debt
+        let position_index = &mut ctx.accounts.position_index;
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.user = ctx.accounts.user.key();
+        stake_account.pool = ctx.accounts.staking_pool.key();
+        stake_account.nonce = position_index.next_nonce;
+        stake_account.amount = amount;
+        stake_account.stake_time = current_time;
+        stake_account.unlock_time = stake_account.stake_time + duration;
+        stake_account.claimed_rewards = 0;
+        stake_account.last_claim_time = current_time;
+        stake_account.auto_compound = false;
+        stake_account.boost_bps = boost_bps;
+        stake_account.weight = weight;
+        stake_account.reward_debt = ctx.accounts.staking_pool.accumulated_rewards(weight)?;
+        stake_account.bump = ctx.bumps.stake_account;
+
+        // List the position so wallets can enumerate it
+        require!(
+            position_index.positions.len() < MAX_STAKE_POSITIONS,
+            ErrorCode::TooManyStakePositions
+        );
+        position_index.user = ctx.accounts.user.key();
+        position_index.positions.push(stake_account.key());
+        position_index.next_nonce += 1;
+        position_index.bump = ctx.bumps.position_index;
+
+        // Update staking pool
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked += amount;
+        staking_pool.total_weight += weight;
+        
+        Ok(())
+    }
+
+    pub fn unstake_tokens(ctx: Context<UnstakeTokens>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+        
+        require!(
+            current_time >= ctx.accounts.stake_account.unlock_time,
+            ErrorCode::StakingPeriodNotEnded
+        );
+
+        // Calculate pending rewards
+        ctx.accounts.staking_pool.update_rewards(current_time)?;
+        let stake_account = &ctx.accounts.stake_account;
+        let pending_rewards = ctx.accounts.staking_pool.pending_rewards(stake_account)?;
+
+        // Store values needed for seeds before any CPI calls
+        let authority_key = ctx.accounts.staking_pool.authority;
+        let staking_pool_bump = ctx.accounts.staking_pool.bump;
+        let stake_amount = stake_account.amount;
+        let stake_weight = stake_account.weight;
+
+        // Transfer staked tokens back
+        let seeds = &[
+            b"staking_pool".as_ref(),
+            authority_key.as_ref(),
+            &[staking_pool_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.staking_vault.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.staking_pool.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+        
+        token::transfer(cpi_ctx, stake_amount)?;
+
+        // Mint rewards if any, capped at the unallocated supply so a full cap never locks the principal
+        let token_data = &ctx.accounts.token_data;
+        let reward_amount = pending_rewards
+            .min(token_data.total_supply.saturating_sub(token_data.allocated_supply))
+            .min(token_data.total_supply.saturating_sub(ctx.accounts.mint.supply));
+        if reward_amount > 0 {
+            mint_staking_rewards(
+                ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.mint,
+                &mut ctx.accounts.token_data,
+                ctx.accounts.user_token_account.to_account_info(),
+                reward_amount,
+            )?;
+        }
+
+        let stake_account_key = ctx.accounts.stake_account.key();
+        ctx.accounts
+            .position_index
+            .positions
+            .retain(|position| *position != stake_account_key);
+
+        // Update staking pool (mutable borrow at the end)
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked -= stake_amount;
+        staking_pool.total_weight -= stake_weight;
+        
+        Ok(())
+    }
+
+    pub fn claim_staking_rewards(ctx: Context<ClaimStakingRewards>) -> Result<()> {
+        let current_time = Clock::get()?.unix_timestamp;
+
+        ctx.accounts.staking_pool.update_rewards(current_time)?;
+        let pending_rewards = ctx
+            .accounts
+            .staking_pool
+            .pending_rewards(&ctx.accounts.stake_account)?;
+        require!(pending_rewards > 0, ErrorCode::NoTokensToClaim);
+
+        mint_staking_rewards(
+            ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.token_data,
+            ctx.accounts.user_token_account.to_account_info(),
+            pending_rewards,
+        )?;
+
+        // Principal stays in the vault until unlock_time
+        let stake_account = &mut ctx.accounts.stake_account;
+        stake_account.reward_debt = ctx
+            .accounts
+            .staking_pool
+            .accumulated_rewards(stake_account.weight)?;
+        stake_account.claimed_rewards = stake_account
+            .claimed_rewards
+            .checked_add(pending_rewards)
+            .ok_or(ErrorCode::MathOverflow)?;
+        stake_account.last_claim_time = current_time;
+
+        Ok(())
+    }
+
+    pub fn set_auto_compound(ctx: Context<SetAutoCompound>, enabled: bool) -> Result<()> {
+        ctx.accounts.stake_account.auto_compound = enabled;
+
+        Ok(())
+    }
+
+    pub fn compound(ctx: Context<Compound>) -> Result<()> {
+        // Keepers may only compound positions that opted in
+        let stake_account = &ctx.accounts.stake_account;
+        require!(
+            ctx.accounts.caller.key() == stake_account.user || stake_account.auto_compound,
+            ErrorCode::AutoCompoundDisabled
+        );
+
+        let current_time = Clock::get()?.unix_timestamp;
+        ctx.accounts.staking_pool.update_rewards(current_time)?;
+        let pending_rewards = ctx
+            .accounts
+            .staking_pool
+            .pending_rewards(&ctx.accounts.stake_account)?;
+        require!(pending_rewards > 0, ErrorCode::NoTokensToClaim);
+
+        // Rewards are minted straight into the vault; principal never moves
+        mint_staking_rewards(
+            ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.mint,
+            &mut ctx.accounts.token_data,
+            ctx.accounts.staking_vault.to_account_info(),
+            pending_rewards,
+        )?;
+
+        // Compounded tokens earn the position's original boost
+        let stake_account = &mut ctx.accounts.stake_account;
+        let previous_weight = stake_account.weight;
+        stake_account.amount = stake_account
+            .amount
+            .checked_add(pending_rewards)
+            .ok_or(ErrorCode::MathOverflow)?;
+        stake_account.weight = calculate_stake_weight(stake_account.amount, stake_account.boost_bps)?;
+
+        let staking_pool = &mut ctx.accounts.staking_pool;
+        staking_pool.total_staked = staking_pool
+            .total_staked
+            .checked_add(pending_rewards)
+            .ok_or(ErrorCode::MathOverflow)?;
+        staking_pool.total_weight = staking_pool
+            .total_weight
+            .checked_add(stake_account.weight - previous_weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+        stake_account.reward_debt = staking_pool.accumulated_rewards(stake_account.weight)?;
+
+        Ok(())
+    }
+}
+
+// Helper functions
+/// Effective stake weight of `amount` at a boost in basis points (10000 = 1x)
+fn calculate_stake_weight(amount: u64, boost_bps: u16) -> Result<u64> {
+    let weight = amount as u128 * boost_bps as u128 / 10000;
+    u64::try_from(weight).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+/// Staking rewards come out of the unallocated budget
+fn mint_staking_rewards<'info>(
+    token_program: AccountInfo<'info>,
+    mint: &Account<'info, Mint>,
+    token_data: &mut Account<'info, TokenData>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    token_data.allocated_supply = reserve_supply(token_data, amount)?;
+    token_data.circulating_supply += amount;
+
+    mint_with_token_data(token_program, mint, token_data, to, amount)
+}
+
+fn mint_with_token_data<'info>(
+    token_program: AccountInfo<'info>,
+    mint: &Account<'info, Mint>,
+    token_data: &Account<'info, TokenData>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    // Hard cap on the SPL supply for every mint, on top of the allocation reservations
+    let minted_supply = mint.supply.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+    require!(minted_supply <= token_data.total_supply, ErrorCode::ExceedsTotalSupply);
+
+    let seeds = &[
+        b"token_data".as_ref(),
+        token_data.mint.as_ref(),
+        &[token_data.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = MintTo {
+        mint: mint.to_account_info(),
+        to,
+        authority: token_data.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer);
+
+    token::mint_to(cpi_ctx, amount)
+}
+
+fn create_allocation_record(
+    token_data: &mut TokenData,
+    allocation_budget: &mut AllocationBudget,
+    allocation_registry: &mut AllocationRegistry,
+    allocation: &mut TokenAllocation,
+    allocation_key: Pubkey,
+    request: &AllocationRequest,
+    schedule: &VestingSchedule,
+) -> Result<u64> {
+    // Reserve the full allocation up front so future vesting can't over-commit the supply
+    token_data.allocated_supply = reserve_supply(token_data, request.amount)?;
+    allocation_budget.reserve(request.allocation_type, request.amount)?;
+
+    let milestone_bps: u64 = request
+        .milestones
+        .iter()
+        .map(|milestone| milestone.tranche_bps as u64)
+        .sum();
+    require!(
+        request.milestones.len() <= MAX_MILESTONES_PER_ALLOCATION
+            && request.milestones.iter().all(|milestone| milestone.tranche_bps > 0)
+            && milestone_bps <= 10000,
+        ErrorCode::InvalidMilestones
+    );
+    allocation.amount = request.amount;
+    allocation.milestones = request
+        .milestones
+        .iter()
+        .map(|milestone| {
+            Ok(Milestone {
+                tranche_bps: milestone.tranche_bps,
+                tranche_amount: mul_div(request.amount, milestone.tranche_bps as u64, 10000)?,
+                attestor: milestone.attestor,
+                attested_at: 0,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Before TGE the unlock stays with the allocation and becomes claimable once TGE passes;
+    // milestone tranches are outside the schedule and don't unlock at TGE
+    let tge_timestamp = token_data.tge_timestamp;
+    let tge_amount = if tge_timestamp != 0 && Clock::get()?.unix_timestamp >= tge_timestamp {
+        mul_div(allocation.time_vested_total()?, schedule.tge_unlock_percentage as u64, 10000)?
+    } else {
+        0
+    };
+
+    // Register the allocation under the recipient
+    require!(
+        allocation_registry.allocations.len() < MAX_ALLOCATIONS_PER_RECIPIENT,
+        ErrorCode::TooManyAllocations
+    );
+    allocation_registry.mint = token_data.mint;
+    allocation_registry.recipient = request.recipient;
+    allocation_registry.allocations.push(allocation_key);
+
+    // Snapshot the category's vesting terms into the allocation; the caller pays out the TGE unlock
+    // and records the rent payer
+    allocation.id = token_data.allocation_count;
+    allocation.mint = token_data.mint;
+    allocation.recipient = request.recipient;
+    allocation.allocation_type = request.allocation_type;
+    allocation.schedule = schedule.clone();
+    allocation.claimed_amount = tge_amount;
+    allocation.revocable = request.revocable;
+    allocation.revoked = false;
+    allocation.revoked_at = 0;
+    allocation.escrowed = false;
+    allocation.crank_opt_out = false;
+    allocation.last_cranked_at = 0;
+    allocation.pending_recipient = Pubkey::default();
+    allocation.vested_base = 0;
+    allocation.progress_base = 0;
+    allocation.amendment_count = 0;
+    allocation.last_amended_at = 0;
+    allocation.frozen = false;
+    allocation.claim_history = Vec::new();
+    allocation.claim_count = 0;
+    allocation.position_mint = Pubkey::default();
+
+    token_data.allocation_count += 1;
+
+    Ok(tge_amount)
+}
+
+fn verify_beneficiary(
+    allocation: &TokenAllocation,
+    claimant: Pubkey,
+    position_token_account: Option<&Account<TokenAccount>>,
+) -> Result<()> {
+    if allocation.position_mint == Pubkey::default() {
+        require_keys_eq!(allocation.recipient, claimant, ErrorCode::NotBeneficiary);
+        return Ok(());
+    }
+
+    // Tokenized allocations belong to whoever holds the position token
+    let position_token_account = position_token_account.ok_or(ErrorCode::NotBeneficiary)?;
+    require!(
+        position_token_account.mint == allocation.position_mint
+            && position_token_account.owner == claimant
+            && position_token_account.amount == 1,
+        ErrorCode::NotBeneficiary
+    );
+
+    Ok(())
+}
+
+fn reassign_beneficiary(
+    allocation: &mut TokenAllocation,
+    allocation_key: Pubkey,
+    current_registry: &mut AllocationRegistry,
+    new_registry: &mut AllocationRegistry,
+    new_recipient: Pubkey,
+) -> Result<()> {
+    // Both registries would be the same account, and the later write would keep the allocation listed
+    require_keys_neq!(new_recipient, allocation.recipient, ErrorCode::SameBeneficiary);
+
+    // Move the allocation between the two recipients' registries
+    current_registry
+        .allocations
+        .retain(|registered| *registered != allocation_key);
+
+    require!(
+        new_registry.allocations.len() < MAX_ALLOCATIONS_PER_RECIPIENT,
+        ErrorCode::TooManyAllocations
+    );
+    new_registry.mint = allocation.mint;
+    new_registry.recipient = new_recipient;
+    new_registry.allocations.push(allocation_key);
+
+    allocation.recipient = new_recipient;
+    allocation.pending_recipient = Pubkey::default();
+
+    Ok(())
+}
+
+fn create_program_account<'info>(
+    payer: AccountInfo<'info>,
+    new_account: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    space: usize,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let signer = &[signer_seeds];
+    let rent_exempt_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = new_account.lamports();
+
+    if current_lamports == 0 {
+        let cpi_accounts = system_program::CreateAccount {
+            from: payer,
+            to: new_account,
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program, cpi_accounts, signer);
+        return system_program::create_account(cpi_ctx, rent_exempt_lamports, space as u64, &crate::ID);
+    }
+
+    // Someone pre-funded the address, so top it up and take it over instead
+    let top_up_lamports = rent_exempt_lamports.saturating_sub(current_lamports);
+    if top_up_lamports > 0 {
+        let cpi_accounts = system_program::Transfer {
+            from: payer,
+            to: new_account.clone(),
+        };
+        let cpi_ctx = CpiContext::new(system_program.clone(), cpi_accounts);
+        system_program::transfer(cpi_ctx, top_up_lamports)?;
+    }
+
+    let cpi_accounts = system_program::Allocate {
+        account_to_allocate: new_account.clone(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program.clone(), cpi_accounts, signer);
+    system_program::allocate(cpi_ctx, space as u64)?;
+
+    let cpi_accounts = system_program::Assign {
+        account_to_assign: new_account,
+    };
+    let cpi_ctx = CpiContext::new_with_signer(system_program, cpi_accounts, signer);
+    system_program::assign(cpi_ctx, &crate::ID)
+}
+
+/// Logs the TGE unlock paid at creation like any other claim, so the history adds up to `claimed_amount`
+fn record_tge_claim(
+    allocation: &mut TokenAllocation,
+    allocation_key: Pubkey,
+    destination: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp;
+    allocation.record_claim(ClaimRecord {
+        timestamp,
+        amount,
+        destination,
+    });
+
+    emit!(TokensClaimed {
+        allocation: allocation_key,
+        recipient: allocation.recipient,
+        destination,
+        amount,
+        timestamp,
+    });
+
+    Ok(())
+}
+
+fn release_vested_tokens<'info>(
+    token_program: AccountInfo<'info>,
+    mint: &Account<'info, Mint>,
+    token_data: &mut Account<'info, TokenData>,
+    allocation_budget: &mut Account<'info, AllocationBudget>,
+    allocation: &mut Account<'info, TokenAllocation>,
+    allocation_vault: Option<&Account<'info, TokenAccount>>,
+    destination: AccountInfo<'info>,
+) -> Result<u64> {
+    require!(!allocation.frozen, ErrorCode::AllocationFrozen);
+
+    let current_time = Clock::get()?.unix_timestamp;
+    let claimable_amount = calculate_claimable_amount(allocation, token_data.tge_timestamp, current_time)?;
+    
+    require!(claimable_amount > 0, ErrorCode::NoTokensToClaim);
+
+    let destination_key = destination.key();
+    if allocation.escrowed {
+        let allocation_vault = allocation_vault.ok_or(ErrorCode::InvalidAllocationVault)?;
+        transfer_with_token_data(
+            token_program,
+            token_data,
+            allocation_vault.to_account_info(),
+            destination,
+            claimable_amount,
+        )?;
+        token_data.locked_supply -= claimable_amount;
+    } else {
+        mint_with_token_data(token_program, mint, token_data, destination, claimable_amount)?;
+        allocation_budget.minted[allocation.allocation_type as usize] += claimable_amount;
+    }
+
+    allocation.claimed_amount += claimable_amount;
+    allocation.record_claim(ClaimRecord {
+        timestamp: current_time,
+        amount: claimable_amount,
+        destination: destination_key,
+    });
+    token_data.circulating_supply += claimable_amount;
+
+    emit!(TokensClaimed {
+        allocation: allocation.key(),
+        recipient: allocation.recipient,
+        destination: destination_key,
+        amount: claimable_amount,
+        timestamp: current_time,
+    });
+
+    Ok(claimable_amount)
+}
+
+fn reserve_supply(token_data: &TokenData, amount: u64) -> Result<u64> {
+    let allocated_supply = token_data
+        .allocated_supply
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    require!(allocated_supply <= token_data.total_supply, ErrorCode::OverAllocation);
+    Ok(allocated_supply)
+}
+
+fn transfer_with_token_data<'info>(
+    token_program: AccountInfo<'info>,
+    token_data: &Account<'info, TokenData>,
+    from: AccountInfo<'info>,
+    to: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"token_data".as_ref(),
+        token_data.mint.as_ref(),
+        &[token_data.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = Transfer {
+        from,
+        to,
+        authority: token_data.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer);
+
+    token::transfer(cpi_ctx, amount)
+}
+
+fn burn_with_token_data<'info>(
+    token_program: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    token_data: &Account<'info, TokenData>,
+    from: AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let seeds = &[
+        b"token_data".as_ref(),
+        token_data.mint.as_ref(),
+        &[token_data.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = Burn {
+        mint,
+        from,
+        authority: token_data.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer);
+
+    token::burn(cpi_ctx, amount)
+}
+
+fn close_with_token_data<'info>(
+    token_program: AccountInfo<'info>,
+    token_data: &Account<'info, TokenData>,
+    account: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+) -> Result<()> {
+    let seeds = &[
+        b"token_data".as_ref(),
+        token_data.mint.as_ref(),
+        &[token_data.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = CloseAccount {
+        account,
+        destination,
+        authority: token_data.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new_with_signer(token_program, cpi_accounts, signer);
+
+    token::close_account(cpi_ctx)
+}
+
+fn validate_vesting_schedule(schedule: &VestingSchedule) -> Result<()> {
+    require!(
+        schedule.cliff_duration >= 0 && schedule.vesting_duration >= 0,
+        ErrorCode::InvalidVestingSchedule
+    );
+    require!(
+        schedule.tge_unlock_percentage <= 10000,
+        ErrorCode::InvalidVestingSchedule
+    );
+
+    match &schedule.curve {
+        VestingCurve::Linear => {}
+        VestingCurve::PeriodicStep { period } => {
+            require!(*period > 0, ErrorCode::InvalidVestingSchedule);
+        }
+        VestingCurve::Table { entries } => {
+            require!(
+                !entries.is_empty() && entries.len() <= MAX_UNLOCK_TABLE_ENTRIES,
+                ErrorCode::InvalidVestingSchedule
+            );
+            // Timestamps strictly increase, cumulative unlocks never decrease and end at 100%
+            for pair in entries.windows(2) {
+                require!(
+                    pair[0].timestamp < pair[1].timestamp
+                        && pair[0].cumulative_bps <= pair[1].cumulative_bps,
+                    ErrorCode::InvalidVestingSchedule
+                );
+            }
+            require!(
+                entries.last().map(|entry| entry.cumulative_bps) == Some(10000),
+                ErrorCode::InvalidVestingSchedule
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn calculate_claimable_amount(
+    allocation: &TokenAllocation,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<u64> {
+    let total_vested = calculate_vested_amount(allocation, tge_timestamp, current_time)?;
+    let claimable = total_vested.saturating_sub(allocation.claimed_amount);
+    Ok(claimable)
+}
+
+fn calculate_vested_amount(
+    allocation: &TokenAllocation,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<u64> {
+    // A revoked allocation was frozen at its vested amount
+    if allocation.revoked {
+        return Ok(allocation.amount);
+    }
+
+    // Attested milestone tranches vest on top of the time-based schedule
+    let time_vested = calculate_time_vested_amount(allocation, tge_timestamp, current_time)?;
+    if tge_timestamp == 0 || current_time < tge_timestamp {
+        return Ok(time_vested);
+    }
+    let (_, attested_amount) = allocation.milestone_amounts()?;
+
+    let total_vested = time_vested
+        .checked_add(attested_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(total_vested)
+}
+
+fn calculate_time_vested_amount(
+    allocation: &TokenAllocation,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<u64> {
+    let time_amount = allocation.time_vested_total()?;
+
+    if allocation.amendment_count > 0 {
+        return calculate_amended_vested_amount(allocation, time_amount, tge_timestamp, current_time);
+    }
+
+    calculate_schedule_vested_amount(time_amount, &allocation.schedule, tge_timestamp, current_time)
+}
+
+fn calculate_amended_vested_amount(
+    allocation: &TokenAllocation,
+    time_amount: u64,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<u64> {
+    // The schedule was already complete at the last amendment
+    if allocation.progress_base >= VESTING_PROGRESS_PRECISION {
+        return Ok(time_amount);
+    }
+
+    // What was unvested at the checkpoint vests with the schedule's remaining progress
+    let progress = calculate_schedule_vested_amount(
+        VESTING_PROGRESS_PRECISION,
+        &allocation.schedule,
+        tge_timestamp,
+        current_time,
+    )?;
+    let progress_since_amendment = progress.saturating_sub(allocation.progress_base);
+    let vested_since_amendment = mul_div(
+        time_amount - allocation.vested_base,
+        progress_since_amendment,
+        VESTING_PROGRESS_PRECISION - allocation.progress_base,
+    )?;
+
+    let total_vested = allocation
+        .vested_base
+        .checked_add(vested_since_amendment)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(total_vested)
+}
+
+fn calculate_schedule_vested_amount(
+    amount: u64,
+    schedule: &VestingSchedule,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<u64> {
+    // Nothing vests, not even the TGE unlock, until TGE has passed
+    if tge_timestamp == 0 || current_time < tge_timestamp {
+        return Ok(0);
+    }
+
+    let tge_amount = mul_div(amount, schedule.tge_unlock_percentage as u64, 10000)?;
+    let vesting_amount = amount - tge_amount;
+    let vesting_start = tge_timestamp
+        .checked_add(schedule.cliff_duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    let schedule_vested = match &schedule.curve {
+        // Continuous linear vesting is a step curve with a one-second period
+        VestingCurve::Linear => {
+            calculate_step_vested_amount(vesting_amount, schedule, vesting_start, current_time, 1)?
+        }
+        VestingCurve::PeriodicStep { period } => {
+            calculate_step_vested_amount(vesting_amount, schedule, vesting_start, current_time, *period)?
+        }
+        // Unlock tables carry absolute timestamps, so cliff and duration don't apply
+        VestingCurve::Table { entries } => {
+            let unlocked_bps = entries
+                .iter()
+                .take_while(|entry| entry.timestamp <= current_time)
+                .last()
+                .map_or(0, |entry| entry.cumulative_bps);
+            mul_div(vesting_amount, unlocked_bps as u64, 10000)?
+        }
+    };
+    
+    let total_vested = tge_amount
+        .checked_add(schedule_vested)
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(total_vested)
+}
+
+/// Returns the next timestamp at which more tokens vest on the time-based schedule and the
+/// timestamp at which the allocation is fully vested; 0 means none, or not yet known
+fn calculate_unlock_timestamps(
+    allocation: &TokenAllocation,
+    tge_timestamp: i64,
+    current_time: i64,
+) -> Result<(i64, i64)> {
+    if allocation.revoked {
+        return Ok((0, allocation.revoked_at));
+    }
+    if tge_timestamp == 0 {
+        return Ok((0, 0));
+    }
+
+    let schedule = &allocation.schedule;
+    let time_amount = allocation.time_vested_total()?;
+    let tge_amount = mul_div(time_amount, schedule.tge_unlock_percentage as u64, 10000)?;
+    let vesting_start = tge_timestamp
+        .checked_add(schedule.cliff_duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Nothing is unlocked before TGE, so earlier schedule points all land on TGE itself
+    let (next_curve_unlock, curve_end) = if tge_amount == time_amount {
+        (None, tge_timestamp)
+    } else {
+        match &schedule.curve {
+            VestingCurve::Linear => next_step_unlock(schedule, vesting_start, current_time, 1)?,
+            VestingCurve::PeriodicStep { period } => {
+                next_step_unlock(schedule, vesting_start, current_time, *period)?
+            }
+            VestingCurve::Table { entries } => (
+                entries
+                    .iter()
+                    .find(|entry| entry.timestamp > current_time)
+                    .map(|entry| entry.timestamp),
+                entries.last().map_or(tge_timestamp, |entry| entry.timestamp),
+            ),
+        }
+    };
+
+    let next_unlock_timestamp = if current_time < tge_timestamp && tge_amount > 0 {
+        tge_timestamp
+    } else {
+        next_curve_unlock.map_or(0, |timestamp| timestamp.max(tge_timestamp))
+    };
+
+    // Unattested milestones have no date, so neither does full vesting
+    let (milestone_amount, attested_amount) = allocation.milestone_amounts()?;
+    let fully_vested_timestamp = if attested_amount < milestone_amount {
+        0
+    } else {
+        curve_end.max(tge_timestamp)
+    };
+
+    Ok((next_unlock_timestamp, fully_vested_timestamp))
+}
+
+fn next_step_unlock(
+    schedule: &VestingSchedule,
+    vesting_start: i64,
+    current_time: i64,
+    period: i64,
+) -> Result<(Option<i64>, i64)> {
+    let vesting_end = vesting_start
+        .checked_add(schedule.vesting_duration)
+        .ok_or(ErrorCode::MathOverflow)?;
+    if current_time >= vesting_end {
+        return Ok((None, vesting_end));
+    }
+
+    // The first whole period after the current one, capped at the end of vesting
+    let elapsed_periods = if current_time < vesting_start {
+        0
+    } else {
+        (current_time - vesting_start) / period + 1
+    };
+    let next_unlock = elapsed_periods
+        .max(1)
+        .checked_mul(period)
+        .and_then(|offset| vesting_start.checked_add(offset))
+        .map_or(vesting_end, |timestamp| timestamp.min(vesting_end));
+
+    Ok((Some(next_unlock), vesting_end))
+}
+
+fn calculate_step_vested_amount(
+    amount: u64,
+    schedule: &VestingSchedule,
+    vesting_start: i64,
+    current_time: i64,
+    period: i64,
+) -> Result<u64> {
+    if current_time < vesting_start {
+        return Ok(0);
+    }
+
+    let elapsed_time = current_time - vesting_start;
+    if elapsed_time >= schedule.vesting_duration {
+        return Ok(amount);
+    }
+
+    // Only whole periods count towards vesting
+    let vested_time = elapsed_time - elapsed_time % period;
+    mul_div(amount, vested_time as u64, schedule.vesting_duration as u64)
+}
+
+fn verify_merkle_proof(proof: &[[u8; 32]], root: [u8; 32], leaf: [u8; 32]) -> bool {
+    // Pairs are hashed in sorted order, so proofs don't need left/right flags
+    let computed_root = proof.iter().fold(leaf, |node, sibling| {
+        let (first, second) = if node <= *sibling { (&node, sibling) } else { (sibling, &node) };
+        hashv(&[&[MERKLE_NODE_PREFIX], first, second]).to_bytes()
+    });
+    computed_root == root
+}
+
+fn mul_div(value: u64, numerator: u64, denominator: u64) -> Result<u64> {
+    let result = (value as u128)
+        .checked_mul(numerator as u128)
+        .and_then(|product| product.checked_div(denominator as u128))
+        .ok_or(ErrorCode::MathOverflow)?;
+    u64::try_from(result).map_err(|_| error!(ErrorCode::MathOverflow))
+}
+
+// Account validation structs
+#[derive(Accounts)]
+pub struct InitializeToken<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = 9,
+        mint::authority = token_data,
+    )]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TokenData::LEN,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + AllocationBudget::LEN,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetTgeTimestamp<'info> {
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+}
+
+#[derive(Accounts)]
+#[instruction(allocation_type: AllocationType)]
+pub struct InitializeVestingTemplate<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+        has_one = mint,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VestingTemplate::LEN,
+        seeds = [b"vesting_template", mint.key().as_ref(), &[allocation_type as u8]],
+        bump
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVestingTemplate<'info> {
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+        has_one = mint,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"vesting_template", mint.key().as_ref(), &[vesting_template.allocation_type as u8]],
+        bump = vesting_template.bump,
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, allocation_type: AllocationType)]
+pub struct MintTokens<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    /// CHECK: Recipient can be any account
+    pub recipient: AccountInfo<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + AllocationRegistry::LEN,
+        seeds = [b"allocation_registry", mint.key().as_ref(), recipient.key().as_ref()],
+        bump
+    )]
+    pub allocation_registry: Account<'info, AllocationRegistry>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TokenAllocation::LEN,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &token_data.allocation_count.to_le_bytes(),
+        ],
+        bump
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        seeds = [b"vesting_template", mint.key().as_ref(), &[allocation_type as u8]],
+        bump = vesting_template.bump,
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+    
+    /// Only passed for escrowed allocations, which are minted up front into this vault
+    #[account(
+        init,
+        payer = authority,
+        token::mint = mint,
+        token::authority = token_data,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump
+    )]
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct BatchMintAllocations<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(merkle_root: [u8; 32], allocation_type: AllocationType)]
+pub struct CreateMerkleDistribution<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        seeds = [b"vesting_template", mint.key().as_ref(), &[allocation_type as u8]],
+        bump = vesting_template.bump,
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + MerkleDistribution::LEN,
+        seeds = [b"merkle_distribution", mint.key().as_ref(), merkle_root.as_ref()],
+        bump
+    )]
+    pub merkle_distribution: Account<'info, MerkleDistribution>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimMerkleAllocation<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"merkle_distribution",
+            mint.key().as_ref(),
+            merkle_distribution.merkle_root.as_ref(),
+        ],
+        bump = merkle_distribution.bump,
+    )]
+    pub merkle_distribution: Account<'info, MerkleDistribution>,
+    
+    /// Created on the recipient's first claim
+    #[account(
+        init_if_needed,
+        payer = recipient,
+        space = 8 + MerkleClaimStatus::LEN,
+        seeds = [b"merkle_claim", merkle_distribution.key().as_ref(), recipient.key().as_ref()],
+        bump
+    )]
+    pub claim_status: Account<'info, MerkleClaimStatus>,
+    
+    #[account(
+        init_if_needed,
+        payer = recipient,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVestedTokens<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump,
+    )]
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    /// Required for tokenized allocations: the claimant's account holding the position token
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct PreviewAllocation<'info> {
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureCrankTip<'info> {
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+}
+
+#[derive(Accounts)]
+pub struct SetCrankOptOut<'info> {
+    pub recipient: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = recipient,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeBeneficiaryChange<'info> {
+    pub recipient: Signer<'info>,
+    
+    /// Co-signs when the category's template requires it
+    pub authority: Option<Signer<'info>>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        seeds = [b"vesting_template", mint.key().as_ref(), &[allocation.allocation_type as u8]],
+        bump = vesting_template.bump,
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = recipient,
+        constraint = allocation.position_mint == Pubkey::default() @ ErrorCode::AllocationTokenized,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptBeneficiaryChange<'info> {
+    #[account(mut)]
+    pub new_recipient: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        constraint = allocation.pending_recipient == new_recipient.key() @ ErrorCode::NotPendingBeneficiary,
+        constraint = !allocation.frozen @ ErrorCode::AllocationFrozen,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_registry", mint.key().as_ref(), allocation.recipient.as_ref()],
+        bump = current_registry.bump,
+    )]
+    pub current_registry: Account<'info, AllocationRegistry>,
+    
+    #[account(
+        init_if_needed,
+        payer = new_recipient,
+        space = 8 + AllocationRegistry::LEN,
+        seeds = [b"allocation_registry", mint.key().as_ref(), new_recipient.key().as_ref()],
+        bump
+    )]
+    pub new_registry: Account<'info, AllocationRegistry>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TokenizeAllocation<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        seeds = [b"vesting_template", mint.key().as_ref(), &[allocation.allocation_type as u8]],
+        bump = vesting_template.bump,
+    )]
+    pub vesting_template: Account<'info, VestingTemplate>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = recipient,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        init,
+        payer = recipient,
+        seeds = [b"position_mint", allocation.key().as_ref()],
+        bump,
+        mint::decimals = 0,
+        mint::authority = token_data,
+    )]
+    pub position_mint: Account<'info, Mint>,
+    
+    #[account(
+        init_if_needed,
+        payer = recipient,
+        associated_token::mint = position_mint,
+        associated_token::authority = recipient,
+    )]
+    pub position_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
-fn calculate_claimable_amount(allocation: &TokenAllocation, current_time: i64) -> Result<u64> {
-    let vesting_start = allocation.vesting_start + allocation.cliff_duration;
+#[derive(Accounts)]
+pub struct ConfigureRecovery<'info> {
+    #[account(mut)]
+    pub recipient: Signer<'info>,
     
-    if current_time < vesting_start {
-        return Ok(0);
-    }
+    pub mint: Account<'info, Mint>,
     
-    let elapsed_time = current_time - vesting_start;
-    let vesting_progress = std::cmp::min(
-        elapsed_time,
-        allocation.vesting_duration,
-    ) as u64;
+    #[account(
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = recipient,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
     
-    let total_vested = if allocation.vesting_duration == 0 {
-        allocation.amount
-    } else {
-        allocation.amount * vesting_progress / (allocation.vesting_duration as u64)
-    };
+    #[account(
+        init_if_needed,
+        payer = recipient,
+        space = 8 + AllocationRecovery::LEN,
+        seeds = [b"allocation_recovery", allocation.key().as_ref()],
+        bump
+    )]
+    pub recovery: Account<'info, AllocationRecovery>,
     
-    let claimable = total_vested.saturating_sub(allocation.claimed_amount);
-    Ok(claimable)
+    pub system_program: Program<'info, System>,
 }
 
-fn calculate_staking_rewards(
-    amount: u64,
-    apy_percentage: u16,
-    stake_time: i64,
-    current_time: i64,
-) -> u64 {
-    let duration_seconds = (current_time - stake_time) as u64;
-    let duration_years = duration_seconds as f64 / (365.25 * 24.0 * 3600.0);
-    let apy = apy_percentage as f64 / 10000.0;
+#[derive(Accounts)]
+pub struct ApproveRecovery<'info> {
+    pub guardian: Signer<'info>,
+    
+    pub allocation: Account<'info, TokenAllocation>,
     
-    let rewards = (amount as f64 * apy * duration_years) as u64;
-    rewards
+    #[account(
+        mut,
+        seeds = [b"allocation_recovery", allocation.key().as_ref()],
+        bump = recovery.bump,
+        has_one = allocation,
+        constraint = recovery.recipient == allocation.recipient @ ErrorCode::RecoveryConfigStale,
+    )]
+    pub recovery: Account<'info, AllocationRecovery>,
 }
 
-// Account validation structs
 #[derive(Accounts)]
-pub struct InitializeToken<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+pub struct CancelRecovery<'info> {
+    /// The current key can veto a recovery during the waiting period
+    pub recipient: Signer<'info>,
+    
+    #[account(has_one = recipient)]
+    pub allocation: Account<'info, TokenAllocation>,
     
     #[account(
-        init,
-        payer = authority,
-        mint::decimals = 9,
-        mint::authority = token_data,
+        mut,
+        seeds = [b"allocation_recovery", allocation.key().as_ref()],
+        bump = recovery.bump,
+        has_one = allocation,
     )]
+    pub recovery: Account<'info, AllocationRecovery>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRecovery<'info> {
+    #[account(mut)]
+    pub new_recipient: Signer<'info>,
+    
     pub mint: Account<'info, Mint>,
     
     #[account(
-        init,
-        payer = authority,
-        space = 8 + TokenData::LEN,
-        seeds = [b"token_data", mint.key().as_ref()],
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        constraint = !allocation.frozen @ ErrorCode::AllocationFrozen,
+        constraint = allocation.position_mint == Pubkey::default() @ ErrorCode::AllocationTokenized,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_recovery", allocation.key().as_ref()],
+        bump = recovery.bump,
+        has_one = allocation,
+        constraint = recovery.recipient == allocation.recipient @ ErrorCode::RecoveryConfigStale,
+        constraint = recovery.pending_recipient == new_recipient.key() @ ErrorCode::RecoveryNotReady,
+    )]
+    pub recovery: Account<'info, AllocationRecovery>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_registry", mint.key().as_ref(), allocation.recipient.as_ref()],
+        bump = current_registry.bump,
+    )]
+    pub current_registry: Account<'info, AllocationRegistry>,
+    
+    #[account(
+        init_if_needed,
+        payer = new_recipient,
+        space = 8 + AllocationRegistry::LEN,
+        seeds = [b"allocation_registry", mint.key().as_ref(), new_recipient.key().as_ref()],
         bump
     )]
-    pub token_data: Account<'info, TokenData>,
+    pub new_registry: Account<'info, AllocationRegistry>,
     
-    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct MintTokens<'info> {
+pub struct CrankClaim<'info> {
+    /// Anyone can crank; they pay for the recipient's token account if it doesn't exist yet
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub cranker: Signer<'info>,
     
     #[account(mut)]
     pub mint: Account<'info, Mint>,
@@ -380,38 +2695,61 @@ pub struct MintTokens<'info> {
         mut,
         seeds = [b"token_data", mint.key().as_ref()],
         bump = token_data.bump,
-        has_one = authority,
     )]
     pub token_data: Account<'info, TokenData>,
     
-    /// CHECK: Recipient can be any account
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = recipient,
+        constraint = allocation.position_mint == Pubkey::default() @ ErrorCode::AllocationTokenized,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    /// CHECK: Checked against the allocation's recipient
     pub recipient: AccountInfo<'info>,
     
     #[account(
         init_if_needed,
-        payer = authority,
+        payer = cranker,
         associated_token::mint = mint,
         associated_token::authority = recipient,
     )]
     pub recipient_token_account: Account<'info, TokenAccount>,
     
     #[account(
-        init,
-        payer = authority,
-        space = 8 + TokenAllocation::LEN,
-        seeds = [b"allocation", recipient.key().as_ref()],
-        bump
+        mut,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump,
     )]
-    pub allocation: Account<'info, TokenAllocation>,
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    /// Receives the crank tip, if any
+    #[account(
+        mut,
+        token::mint = mint,
+    )]
+    pub cranker_token_account: Option<Account<'info, TokenAccount>>,
     
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct ClaimVestedTokens<'info> {
+pub struct ClaimAllVestedTokens<'info> {
     #[account(mut)]
     pub recipient: Signer<'info>,
     
@@ -427,11 +2765,17 @@ pub struct ClaimVestedTokens<'info> {
     
     #[account(
         mut,
-        seeds = [b"allocation", recipient.key().as_ref()],
-        bump = allocation.bump,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        seeds = [b"allocation_registry", mint.key().as_ref(), recipient.key().as_ref()],
+        bump = allocation_registry.bump,
         has_one = recipient,
     )]
-    pub allocation: Account<'info, TokenAllocation>,
+    pub allocation_registry: Account<'info, AllocationRegistry>,
     
     #[account(
         mut,
@@ -443,6 +2787,187 @@ pub struct ClaimVestedTokens<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct RevokeAllocation<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump,
+    )]
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        token::mint = mint,
+    )]
+    pub treasury_token_account: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct CloseAllocation<'info> {
+    /// Whoever paid the allocation's rent gets it back
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_registry", mint.key().as_ref(), allocation.recipient.as_ref()],
+        bump = allocation_registry.bump,
+    )]
+    pub allocation_registry: Account<'info, AllocationRegistry>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+        has_one = payer,
+        close = payer,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump,
+    )]
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct AttestMilestone<'info> {
+    pub attestor: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+}
+
+#[derive(Accounts)]
+pub struct SetAllocationFrozen<'info> {
+    pub authority: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+}
+
+#[derive(Accounts)]
+pub struct AmendAllocation<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_budget", mint.key().as_ref()],
+        bump = allocation_budget.bump,
+    )]
+    pub allocation_budget: Account<'info, AllocationBudget>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"allocation",
+            mint.key().as_ref(),
+            &allocation.id.to_le_bytes(),
+        ],
+        bump = allocation.bump,
+    )]
+    pub allocation: Account<'info, TokenAllocation>,
+    
+    #[account(
+        mut,
+        seeds = [b"allocation_vault", allocation.key().as_ref()],
+        bump,
+    )]
+    pub allocation_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct BurnTokens<'info> {
     #[account(mut)]
@@ -483,6 +3008,14 @@ pub struct InitializeStaking<'info> {
     
     pub mint: Account<'info, Mint>,
     
+    /// Only the token authority can open a pool, since its rewards are minted from the supply
+    #[account(
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+        has_one = authority,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
     #[account(
         init,
         payer = authority,
@@ -507,6 +3040,19 @@ pub struct InitializeStaking<'info> {
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateStakingApy<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_pool", authority.key().as_ref()],
+        bump = staking_pool.bump,
+        has_one = authority,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+}
+
 #[derive(Accounts)]
 pub struct StakeTokens<'info> {
     #[account(mut)]
@@ -518,6 +3064,7 @@ pub struct StakeTokens<'info> {
         mut,
         seeds = [b"staking_pool", staking_pool.authority.as_ref()],
         bump = staking_pool.bump,
+        has_one = mint,
     )]
     pub staking_pool: Account<'info, StakingPool>,
     
@@ -535,11 +3082,25 @@ pub struct StakeTokens<'info> {
     )]
     pub staking_vault: Account<'info, TokenAccount>,
     
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + StakePositionIndex::LEN,
+        seeds = [b"stake_position_index", user.key().as_ref()],
+        bump
+    )]
+    pub position_index: Account<'info, StakePositionIndex>,
+    
     #[account(
         init,
         payer = user,
         space = 8 + StakeAccount::LEN,
-        seeds = [b"stake_account", user.key().as_ref()],
+        seeds = [
+            b"stake_account",
+            staking_pool.key().as_ref(),
+            user.key().as_ref(),
+            &position_index.next_nonce.to_le_bytes(),
+        ],
         bump
     )]
     pub stake_account: Account<'info, StakeAccount>,
@@ -558,6 +3119,7 @@ pub struct UnstakeTokens<'info> {
     pub mint: Account<'info, Mint>,
     
     #[account(
+        mut,
         seeds = [b"token_data", mint.key().as_ref()],
         bump = token_data.bump,
     )]
@@ -567,6 +3129,7 @@ pub struct UnstakeTokens<'info> {
         mut,
         seeds = [b"staking_pool", staking_pool.authority.as_ref()],
         bump = staking_pool.bump,
+        has_one = mint,
     )]
     pub staking_pool: Account<'info, StakingPool>,
     
@@ -586,7 +3149,19 @@ pub struct UnstakeTokens<'info> {
     
     #[account(
         mut,
-        seeds = [b"stake_account", user.key().as_ref()],
+        seeds = [b"stake_position_index", user.key().as_ref()],
+        bump = position_index.bump,
+    )]
+    pub position_index: Account<'info, StakePositionIndex>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"stake_account",
+            staking_pool.key().as_ref(),
+            user.key().as_ref(),
+            &stake_account.nonce.to_le_bytes(),
+        ],
         bump = stake_account.bump,
         has_one = user,
         close = user
@@ -596,6 +3171,114 @@ pub struct UnstakeTokens<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct ClaimStakingRewards<'info> {
+    pub user: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_pool", staking_pool.authority.as_ref()],
+        bump = staking_pool.bump,
+        has_one = mint,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = user,
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"stake_account",
+            staking_pool.key().as_ref(),
+            user.key().as_ref(),
+            &stake_account.nonce.to_le_bytes(),
+        ],
+        bump = stake_account.bump,
+        has_one = user,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetAutoCompound<'info> {
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"stake_account",
+            stake_account.pool.as_ref(),
+            user.key().as_ref(),
+            &stake_account.nonce.to_le_bytes(),
+        ],
+        bump = stake_account.bump,
+        has_one = user,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+#[derive(Accounts)]
+pub struct Compound<'info> {
+    /// The position owner, or any keeper if the position enabled auto-compounding
+    pub caller: Signer<'info>,
+    
+    #[account(mut)]
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"token_data", mint.key().as_ref()],
+        bump = token_data.bump,
+    )]
+    pub token_data: Account<'info, TokenData>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_pool", staking_pool.authority.as_ref()],
+        bump = staking_pool.bump,
+        has_one = mint,
+    )]
+    pub staking_pool: Account<'info, StakingPool>,
+    
+    #[account(
+        mut,
+        seeds = [b"staking_vault", staking_pool.authority.as_ref()],
+        bump
+    )]
+    pub staking_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [
+            b"stake_account",
+            staking_pool.key().as_ref(),
+            stake_account.user.as_ref(),
+            &stake_account.nonce.to_le_bytes(),
+        ],
+        bump = stake_account.bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
 // Data structures
 #[account]
 pub struct TokenData {
@@ -604,59 +3287,434 @@ pub struct TokenData {
     pub total_supply: u64,
     pub circulating_supply: u64,
     pub burned_supply: u64,
+    pub allocation_count: u64,
+    pub tge_timestamp: i64,
+    /// Tokens minted into allocation vaults that have not vested yet;
+    /// `circulating_supply` is the unlocked supply
+    pub locked_supply: u64,
+    /// Supply committed to allocations (vested or not) and minted rewards
+    pub allocated_supply: u64,
+    pub crank_tip_amount: u64,
+    pub crank_tip_budget: u64,
+    /// Minimum seconds between crank claims on the same allocation
+    pub crank_interval: i64,
     pub bump: u8,
 }
 
 impl TokenData {
-    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 1;
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1;
+}
+
+/// Crank interval until the authority configures one
+pub const DEFAULT_CRANK_INTERVAL: i64 = 3600;
+
+pub const ALLOCATION_TYPE_COUNT: usize = 7;
+
+/// Per-category caps from the published tokenomics, indexed by `AllocationType`
+#[account]
+pub struct AllocationBudget {
+    pub mint: Pubkey,
+    pub caps: [u64; ALLOCATION_TYPE_COUNT],
+    pub allocated: [u64; ALLOCATION_TYPE_COUNT],
+    pub minted: [u64; ALLOCATION_TYPE_COUNT],
+    pub bump: u8,
+}
+
+impl AllocationBudget {
+    pub const LEN: usize = 32 + 8 * ALLOCATION_TYPE_COUNT * 3 + 1;
+
+    pub fn reserve(&mut self, allocation_type: AllocationType, amount: u64) -> Result<()> {
+        let category = allocation_type as usize;
+        let allocated = self.allocated[category]
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(allocated <= self.caps[category], ErrorCode::CategoryBudgetExceeded);
+        self.allocated[category] = allocated;
+        Ok(())
+    }
 }
 
 #[account]
 pub struct TokenAllocation {
+    pub id: u64,
+    pub mint: Pubkey,
     pub recipient: Pubkey,
     pub amount: u64,
     pub allocation_type: AllocationType,
-    pub vesting_start: i64,
-    pub cliff_duration: i64,
-    pub vesting_duration: i64,
-    pub tge_unlock_percentage: u16,
+    pub schedule: VestingSchedule,
     pub claimed_amount: u64,
+    pub revocable: bool,
+    pub revoked: bool,
+    pub revoked_at: i64,
+    pub escrowed: bool,
+    pub crank_opt_out: bool,
+    pub last_cranked_at: i64,
+    /// Paid the account's rent and receives it back on `close_allocation`
+    pub payer: Pubkey,
+    /// Proposed new beneficiary, or the default key when no change is pending
+    pub pending_recipient: Pubkey,
+    /// Amount vested at the last amendment; the rest vests with the schedule's remaining progress
+    pub vested_base: u64,
+    /// Schedule progress at the last amendment, out of `VESTING_PROGRESS_PRECISION`
+    pub progress_base: u64,
+    pub amendment_count: u32,
+    pub last_amended_at: i64,
+    /// Compliance hold; claims fail while set but vesting keeps accruing
+    pub frozen: bool,
+    /// Tranches that unlock on attestation instead of following `schedule`
+    pub milestones: Vec<Milestone>,
+    /// The most recent claims; once full, entry `claim_count % CLAIM_HISTORY_LEN` is overwritten next
+    pub claim_history: Vec<ClaimRecord>,
+    pub claim_count: u64,
+    /// 1-of-1 position token whose holder is the beneficiary, or the default key if not tokenized
+    pub position_mint: Pubkey,
     pub bump: u8,
 }
 
 impl TokenAllocation {
-    pub const LEN: usize = 32 + 8 + 1 + 8 + 8 + 8 + 2 + 8 + 1;
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 1 + VestingSchedule::LEN + 8 + 1 + 1 + 8 + 1 + 1 + 8 + 32 + 32 + 8 + 8 + 4 + 8 + 1
+        + 4 + Milestone::LEN * MAX_MILESTONES_PER_ALLOCATION
+        + 4 + ClaimRecord::LEN * CLAIM_HISTORY_LEN + 8 + 32 + 1;
+
+    pub fn record_claim(&mut self, record: ClaimRecord) {
+        if self.claim_history.len() < CLAIM_HISTORY_LEN {
+            self.claim_history.push(record);
+        } else {
+            self.claim_history[(self.claim_count % CLAIM_HISTORY_LEN as u64) as usize] = record;
+        }
+        self.claim_count += 1;
+    }
+
+    /// Total of the milestone tranches and the attested part of it
+    pub fn milestone_amounts(&self) -> Result<(u64, u64)> {
+        let mut milestone_amount = 0u64;
+        let mut attested_amount = 0u64;
+        for milestone in &self.milestones {
+            milestone_amount += milestone.tranche_amount;
+            if milestone.attested_at != 0 {
+                attested_amount += milestone.tranche_amount;
+            }
+        }
+        Ok((milestone_amount, attested_amount))
+    }
+
+    /// The part of `amount` that vests on the time-based schedule
+    pub fn time_vested_total(&self) -> Result<u64> {
+        let (milestone_amount, _) = self.milestone_amounts()?;
+        Ok(self.amount - milestone_amount)
+    }
+}
+
+pub const MAX_MILESTONES_PER_ALLOCATION: usize = 8;
+
+pub const CLAIM_HISTORY_LEN: usize = 16;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ClaimRecord {
+    pub timestamp: i64,
+    pub amount: u64,
+    pub destination: Pubkey,
+}
+
+impl ClaimRecord {
+    pub const LEN: usize = 8 + 8 + 32;
+}
+
+#[event]
+pub struct TokensClaimed {
+    pub allocation: Pubkey,
+    pub recipient: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Milestone {
+    pub tranche_bps: u16,
+    /// Fixed when the allocation is created, so amendments only resize the time-based part
+    pub tranche_amount: u64,
+    pub attestor: Pubkey,
+    /// 0 until the attestor signs off
+    pub attested_at: i64,
+}
+
+impl Milestone {
+    pub const LEN: usize = 2 + 8 + 32 + 8;
+}
+
+pub const VESTING_PROGRESS_PRECISION: u64 = 1_000_000_000_000;
+
+pub const MAX_ALLOCATIONS_PER_RECIPIENT: usize = 16;
+
+#[account]
+pub struct AllocationRegistry {
+    pub mint: Pubkey,
+    pub recipient: Pubkey,
+    pub allocations: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl AllocationRegistry {
+    pub const LEN: usize = 32 + 32 + 4 + 32 * MAX_ALLOCATIONS_PER_RECIPIENT + 1;
+}
+
+pub const MAX_RECOVERY_GUARDIANS: usize = 5;
+
+/// Shortest waiting period, so the current key always has a chance to cancel
+pub const MIN_RECOVERY_DELAY: i64 = 24 * 3600;
+
+/// Guardian-based lost-key recovery for one allocation
+#[account]
+pub struct AllocationRecovery {
+    pub allocation: Pubkey,
+    /// Recipient that set up the guardians; they don't carry over to a new beneficiary
+    pub recipient: Pubkey,
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,
+    /// Waiting period after the threshold is met, during which the current key can cancel
+    pub recovery_delay: i64,
+    /// Key that met the threshold and is waiting out `recovery_delay`
+    pub pending_recipient: Pubkey,
+    /// `approvals[i]` is the key `guardians[i]` backs, or the default key
+    pub approvals: Vec<Pubkey>,
+    pub threshold_met_at: i64,
+    pub bump: u8,
+}
+
+impl AllocationRecovery {
+    pub const LEN: usize = 32 + 32 + 4 + 32 * MAX_RECOVERY_GUARDIANS + 1 + 8 + 32
+        + 4 + 32 * MAX_RECOVERY_GUARDIANS + 8 + 1;
+
+    pub fn clear_pending(&mut self) {
+        self.pending_recipient = Pubkey::default();
+        self.approvals = vec![Pubkey::default(); self.guardians.len()];
+        self.threshold_met_at = 0;
+    }
+}
+
+#[account]
+pub struct VestingTemplate {
+    pub mint: Pubkey,
+    pub allocation_type: AllocationType,
+    pub schedule: VestingSchedule,
+    pub beneficiary_change_requires_authority: bool,
+    pub position_tokens_allowed: bool,
+    pub bump: u8,
+}
+
+impl VestingTemplate {
+    pub const LEN: usize = 32 + 1 + VestingSchedule::LEN + 1 + 1 + 1;
+}
+
+pub const MERKLE_LEAF_PREFIX: u8 = 0;
+pub const MERKLE_NODE_PREFIX: u8 = 1;
+
+/// Allocations for a large recipient set, committed to as a merkle root over
+/// (recipient, amount, allocation type) leaves
+#[account]
+pub struct MerkleDistribution {
+    pub mint: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub allocation_type: AllocationType,
+    pub schedule: VestingSchedule,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    pub bump: u8,
+}
+
+impl MerkleDistribution {
+    pub const LEN: usize = 32 + 32 + 1 + VestingSchedule::LEN + 8 + 8 + 1;
+}
+
+#[account]
+pub struct MerkleClaimStatus {
+    pub distribution: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub claimed_amount: u64,
+    pub bump: u8,
+}
+
+impl MerkleClaimStatus {
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 1;
 }
 
 #[account]
 pub struct StakingPool {
     pub authority: Pubkey,
+    pub mint: Pubkey,
     pub apy_percentage: u16,
     pub min_stake_duration: i64,
     pub total_staked: u64,
+    /// Sum of every position's boosted stake weight
+    pub total_weight: u64,
+    /// Lock-duration boosts, ascending by `min_duration`
+    pub tiers: Vec<StakingTier>,
+    /// Rewards earned per unit of stake weight since the pool opened, scaled by `REWARD_PRECISION`
+    pub acc_reward_per_share: u128,
+    pub last_update_time: i64,
     pub bump: u8,
 }
 
 impl StakingPool {
-    pub const LEN: usize = 32 + 2 + 8 + 8 + 1;
+    pub const LEN: usize = 32 + 32 + 2 + 8 + 8 + 8 + 4 + StakingTier::LEN * MAX_STAKING_TIERS + 16 + 8 + 1;
+
+    /// Boost of the longest tier `duration` qualifies for; 1x without one
+    pub fn boost_for_duration(&self, duration: i64) -> u16 {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| duration >= tier.min_duration)
+            .map_or(10000, |tier| tier.boost_bps)
+    }
+
+    /// Accrues `apy_percentage` for the time since the last update
+    pub fn update_rewards(&mut self, current_time: i64) -> Result<()> {
+        let elapsed = current_time.saturating_sub(self.last_update_time).max(0) as u128;
+        let reward_per_share = elapsed
+            .checked_mul(self.apy_percentage as u128)
+            .and_then(|value| value.checked_mul(REWARD_PRECISION))
+            .ok_or(ErrorCode::MathOverflow)?
+            / (10000 * SECONDS_PER_YEAR);
+        self.acc_reward_per_share = self
+            .acc_reward_per_share
+            .checked_add(reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.last_update_time = current_time;
+        Ok(())
+    }
+
+    /// Rewards `weight` would have earned since the pool opened
+    pub fn accumulated_rewards(&self, weight: u64) -> Result<u128> {
+        let rewards = (weight as u128)
+            .checked_mul(self.acc_reward_per_share)
+            .ok_or(ErrorCode::MathOverflow)?
+            / REWARD_PRECISION;
+        Ok(rewards)
+    }
+
+    pub fn pending_rewards(&self, stake_account: &StakeAccount) -> Result<u64> {
+        let pending = self
+            .accumulated_rewards(stake_account.weight)?
+            .saturating_sub(stake_account.reward_debt);
+        u64::try_from(pending).map_err(|_| error!(ErrorCode::MathOverflow))
+    }
+}
+
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+pub const MAX_STAKING_TIERS: usize = 8;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StakingTier {
+    /// Shortest lock that qualifies for this tier
+    pub min_duration: i64,
+    /// Reward multiplier in basis points (10000 = 1x)
+    pub boost_bps: u16,
+}
+
+impl StakingTier {
+    pub const LEN: usize = 8 + 2;
 }
+pub const SECONDS_PER_YEAR: u128 = 31_557_600;
 
 #[account]
 pub struct StakeAccount {
     pub user: Pubkey,
+    pub pool: Pubkey,
+    /// Per-user position number from `StakePositionIndex.next_nonce`
+    pub nonce: u64,
     pub amount: u64,
     pub stake_time: i64,
     pub unlock_time: i64,
     pub claimed_rewards: u64,
+    pub last_claim_time: i64,
+    /// Lets keepers call `compound` on this position
+    pub auto_compound: bool,
+    /// Lock-duration boost from the pool's tier table at stake time
+    pub boost_bps: u16,
+    /// `amount` scaled by `boost_bps`; rewards accrue on this
+    pub weight: u64,
+    /// Accumulated rewards already accounted for, as of the last settlement
+    pub reward_debt: u128,
     pub bump: u8,
 }
 
 impl StakeAccount {
-    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 1;
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 2 + 8 + 16 + 1;
 }
 
-// Enums
+pub const MAX_STAKE_POSITIONS: usize = 32;
+
+/// A user's open stake positions across all pools
+#[account]
+pub struct StakePositionIndex {
+    pub user: Pubkey,
+    pub next_nonce: u64,
+    pub positions: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl StakePositionIndex {
+    pub const LEN: usize = 32 + 8 + 4 + 32 * MAX_STAKE_POSITIONS + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VestingSchedule {
+    pub cliff_duration: i64,
+    pub vesting_duration: i64,
+    pub tge_unlock_percentage: u16,
+    pub curve: VestingCurve,
+}
+
+impl VestingSchedule {
+    pub const LEN: usize = 8 + 8 + 2 + VestingCurve::LEN;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AllocationRequest {
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub allocation_type: AllocationType,
+    pub revocable: bool,
+    pub milestones: Vec<MilestoneTerms>,
+}
+
+/// A milestone tranche of the allocation amount and who can attest it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MilestoneTerms {
+    pub tranche_bps: u16,
+    pub attestor: Pubkey,
+}
+
+/// Returned by `preview_allocation`; timestamps are 0 when there is nothing left to unlock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AllocationPreview {
+    pub vested_amount: u64,
+    pub claimed_amount: u64,
+    pub claimable_amount: u64,
+    pub next_unlock_timestamp: i64,
+    pub fully_vested_timestamp: i64,
+}
+
+/// Recipient, recipient token account, registry, allocation and vesting template
+pub const BATCH_ACCOUNTS_PER_ALLOCATION: usize = 5;
+
+pub const MAX_UNLOCK_TABLE_ENTRIES: usize = 12;
+
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub struct UnlockTableEntry {
+    pub timestamp: i64,
+    pub cumulative_bps: u16,
+}
+
+impl UnlockTableEntry {
+    pub const LEN: usize = 8 + 2;
+}
+
+// Enums
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
 pub enum AllocationType {
     TokenSale,
     TeamAdvisors,
@@ -667,6 +3725,27 @@ pub enum AllocationType {
     Marketing,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum AllocationAmendment {
+    /// Raise or cut the total; it can't drop below what has already vested
+    ChangeAmount { new_amount: u64 },
+    ExtendVesting { additional_duration: i64 },
+    /// Vest a share of the unvested amount now, e.g. 10000 for full acceleration
+    Accelerate { acceleration_bps: u16 },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum VestingCurve {
+    Linear,
+    PeriodicStep { period: i64 },
+    Table { entries: Vec<UnlockTableEntry> },
+}
+
+impl VestingCurve {
+    // Sized for the largest variant, an unlock table
+    pub const LEN: usize = 1 + 4 + UnlockTableEntry::LEN * MAX_UNLOCK_TABLE_ENTRIES;
+}
+
 // Error codes
 #[error_code]
 pub enum ErrorCode {
@@ -680,4 +3759,80 @@ pub enum ErrorCode {
     InsufficientStakeDuration,
     #[msg("Staking period has not ended")]
     StakingPeriodNotEnded,
+    #[msg("Invalid vesting schedule")]
+    InvalidVestingSchedule,
+    #[msg("Recipient has reached the maximum number of allocations")]
+    TooManyAllocations,
+    #[msg("Allocation is not registered to this recipient")]
+    AllocationNotRegistered,
+    #[msg("Allocation is not revocable")]
+    AllocationNotRevocable,
+    #[msg("Allocation has already been revoked")]
+    AllocationAlreadyRevoked,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("TGE timestamp has already been set")]
+    TgeAlreadySet,
+    #[msg("Invalid TGE timestamp")]
+    InvalidTgeTimestamp,
+    #[msg("Missing or invalid allocation vault")]
+    InvalidAllocationVault,
+    #[msg("Allocation exceeds the unallocated supply")]
+    OverAllocation,
+    #[msg("Category budgets exceed the total supply")]
+    InvalidAllocationBudget,
+    #[msg("Allocation exceeds its category budget")]
+    CategoryBudgetExceeded,
+    #[msg("Recipient has opted out of crank claims")]
+    CrankOptedOut,
+    #[msg("Batch accounts don't match the requested allocations")]
+    InvalidBatchAccounts,
+    #[msg("Invalid merkle proof")]
+    InvalidMerkleProof,
+    #[msg("Claim exceeds the merkle distribution total")]
+    MerkleDistributionExhausted,
+    #[msg("Allocation still has unclaimed tokens")]
+    AllocationNotSettled,
+    #[msg("Beneficiary changes for this allocation type need the authority's signature")]
+    AuthorityCosignRequired,
+    #[msg("Signer is not the pending beneficiary")]
+    NotPendingBeneficiary,
+    #[msg("Invalid allocation amendment")]
+    InvalidAmendment,
+    #[msg("Allocation is frozen")]
+    AllocationFrozen,
+    #[msg("Invalid milestones")]
+    InvalidMilestones,
+    #[msg("Signer is not the milestone's attestor")]
+    InvalidMilestoneAttestor,
+    #[msg("Milestone has already been attested")]
+    MilestoneAlreadyAttested,
+    #[msg("Invalid recovery configuration")]
+    InvalidRecoveryConfig,
+    #[msg("Signer is not a recovery guardian")]
+    NotRecoveryGuardian,
+    #[msg("Recovery threshold or waiting period not met")]
+    RecoveryNotReady,
+    #[msg("Signer is not the allocation's beneficiary")]
+    NotBeneficiary,
+    #[msg("Allocation is represented by a position token")]
+    AllocationTokenized,
+    #[msg("Position tokens are disabled for this allocation type")]
+    PositionTokensDisabled,
+    #[msg("User has reached the maximum number of stake positions")]
+    TooManyStakePositions,
+    #[msg("Position has not enabled auto-compounding")]
+    AutoCompoundDisabled,
+    #[msg("Staking tiers must be ascending by duration with boosts of at least 10000 bps")]
+    InvalidStakingTiers,
+    #[msg("Invalid crank interval")]
+    InvalidCrankInterval,
+    #[msg("Allocation was cranked too recently")]
+    CrankCooldown,
+    #[msg("New beneficiary is already the allocation's beneficiary")]
+    SameBeneficiary,
+    #[msg("Recovery was configured by a previous beneficiary")]
+    RecoveryConfigStale,
+    #[msg("A recovery is already waiting out its delay")]
+    RecoveryInProgress,
 }
\ No newline at end of file
@@ -13,8 +13,11 @@ import {
   getAssociatedTokenAddress,
   getAccount,
   getMint,
+  createAssociatedTokenAccount,
+  transfer,
 } from "@solana/spl-token";
 import { expect } from "chai";
+import { createHash } from "crypto";
 
 describe("vtr-token", () => {
   // Configure the client to use the local cluster
@@ -29,6 +32,7 @@ describe("vtr-token", () => {
   let stakingPool: PublicKey;
   let stakingVault: PublicKey;
   let burnVault: PublicKey;
+  let allocationBudget: PublicKey;
 
   // Tokenomics values from the document
   const TOTAL_SUPPLY = new anchor.BN("2000000000000000000"); // 2B tokens with 9 decimals
@@ -43,6 +47,46 @@ describe("vtr-token", () => {
   const BUYBACK_BURN_ALLOCATION = new anchor.BN("200000000000000000"); // 200M tokens
   const MARKETING_ALLOCATION = new anchor.BN("100000000000000000"); // 100M tokens
 
+  // Per-category budgets, in `AllocationType` order
+  const CATEGORY_CAPS = [
+    TOKEN_SALE_ALLOCATION,
+    TEAM_ADVISORS_ALLOCATION,
+    ECOSYSTEM_ALLOCATION,
+    LIQUIDITY_ALLOCATION,
+    PLATFORM_RESERVE_ALLOCATION,
+    BUYBACK_BURN_ALLOCATION,
+    MARKETING_ALLOCATION,
+  ];
+
+  // Published vesting terms per allocation category, in `AllocationType` order
+  const MONTH = 30 * 24 * 3600;
+  const VESTING_TEMPLATES = [
+    { allocationType: { tokenSale: {} }, cliff: 0, duration: 12 * MONTH, tge: 1000 },
+    { allocationType: { teamAdvisors: {} }, cliff: 12 * MONTH, duration: 36 * MONTH, tge: 0 },
+    { allocationType: { ecosystemGrowth: {} }, cliff: 0, duration: 48 * MONTH, tge: 1500 },
+    { allocationType: { liquidity: {} }, cliff: 0, duration: 6 * MONTH, tge: 5000 },
+    { allocationType: { platformReserve: {} }, cliff: 6 * MONTH, duration: 60 * MONTH, tge: 500 },
+    { allocationType: { buybackBurn: {} }, cliff: 0, duration: 24 * MONTH, tge: 0 },
+    { allocationType: { marketing: {} }, cliff: 0, duration: 18 * MONTH, tge: 2000 },
+  ];
+  const TOKEN_SALE = 0;
+  const TEAM_ADVISORS = 1;
+  const ECOSYSTEM_GROWTH = 2;
+  const LIQUIDITY = 3;
+  const PLATFORM_RESERVE = 4;
+  const BUYBACK_BURN = 5;
+  const MARKETING = 6;
+
+  const findVestingTemplate = (allocationTypeIndex: number): PublicKey =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("vesting_template"),
+        mint.publicKey.toBuffer(),
+        Buffer.from([allocationTypeIndex]),
+      ],
+      program.programId
+    )[0];
+
   before(async () => {
     // Generate mint keypair
     mint = Keypair.generate();
@@ -63,6 +107,11 @@ describe("vtr-token", () => {
       program.programId
     );
 
+    [allocationBudget] = await PublicKey.findProgramAddress(
+      [Buffer.from("allocation_budget"), mint.publicKey.toBuffer()],
+      program.programId
+    );
+
     [burnVault] = await PublicKey.findProgramAddress(
       [Buffer.from("burn_vault"), mint.publicKey.toBuffer()],
       program.programId
@@ -75,15 +124,49 @@ describe("vtr-token", () => {
     console.log("- Staking Pool PDA:", stakingPool.toString());
   });
 
+  const findAllocationRegistry = (recipient: PublicKey): PublicKey =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("allocation_registry"),
+        mint.publicKey.toBuffer(),
+        recipient.toBuffer(),
+      ],
+      program.programId
+    )[0];
+
+  const findAllocationVault = (allocation: PublicKey): PublicKey =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("allocation_vault"), allocation.toBuffer()],
+      program.programId
+    )[0];
+
+  // Allocations are addressed by id alone, so they can change beneficiary
+  const findAllocation = (id: anchor.BN): PublicKey =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("allocation"),
+        mint.publicKey.toBuffer(),
+        id.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  // The next allocation is addressed by the global allocation counter
+  const findNextAllocation = async (): Promise<PublicKey> => {
+    const { allocationCount } = await program.account.tokenData.fetch(tokenData);
+    return findAllocation(allocationCount);
+  };
+
   it("Initialize token", async () => {
     console.log("\n=== Testing Token Initialization ===");
 
     const tx = await program.methods
-      .initializeToken(TOTAL_SUPPLY)
+      .initializeToken(TOTAL_SUPPLY, CATEGORY_CAPS)
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
+        allocationBudget,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
         rent: anchor.web3.SYSVAR_RENT_PUBKEY,
@@ -114,6 +197,176 @@ describe("vtr-token", () => {
     expect(mintAccount.mintAuthority?.equals(tokenData)).to.be.true;
   });
 
+  it("Initialize vesting templates", async () => {
+    console.log("\n=== Testing Vesting Template Initialization ===");
+
+    for (const [index, template] of VESTING_TEMPLATES.entries()) {
+      await program.methods
+        .initializeVestingTemplate(template.allocationType, {
+          cliffDuration: new anchor.BN(template.cliff),
+          vestingDuration: new anchor.BN(template.duration),
+          tgeUnlockPercentage: template.tge,
+          curve: { linear: {} },
+        })
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          vestingTemplate: findVestingTemplate(index),
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    const marketingTemplate = await program.account.vestingTemplate.fetch(
+      findVestingTemplate(MARKETING)
+    );
+    expect(marketingTemplate.schedule.tgeUnlockPercentage).to.equal(2000);
+    expect(
+      marketingTemplate.schedule.vestingDuration.eq(new anchor.BN(18 * MONTH))
+    ).to.be.true;
+  });
+
+  it("Update a vesting template", async () => {
+    console.log("\n=== Testing Vesting Template Update ===");
+
+    const marketingTemplate = findVestingTemplate(MARKETING);
+
+    // Extend Marketing from 18 to 24 months without redeploying
+    await program.methods
+      .updateVestingTemplate({
+        cliffDuration: new anchor.BN(0),
+        vestingDuration: new anchor.BN(24 * MONTH),
+        tgeUnlockPercentage: 2000,
+        curve: { linear: {} },
+      })
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        vestingTemplate: marketingTemplate,
+      })
+      .rpc();
+
+    const templateAccount = await program.account.vestingTemplate.fetch(
+      marketingTemplate
+    );
+    expect(templateAccount.schedule.vestingDuration.eq(new anchor.BN(24 * MONTH)))
+      .to.be.true;
+
+    // Rejects a TGE unlock above 100%
+    try {
+      await program.methods
+        .updateVestingTemplate({
+          cliffDuration: new anchor.BN(0),
+          vestingDuration: new anchor.BN(24 * MONTH),
+          tgeUnlockPercentage: 10001,
+          curve: { linear: {} },
+        })
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          vestingTemplate: marketingTemplate,
+        })
+        .rpc();
+      expect.fail("Expected InvalidVestingSchedule");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidVestingSchedule");
+    }
+  });
+
+  it("Configure step and table vesting curves", async () => {
+    console.log("\n=== Testing Vesting Curves ===");
+
+    const buybackTemplate = findVestingTemplate(BUYBACK_BURN);
+
+    // Monthly tranches over the 24-month distribution schedule
+    await program.methods
+      .updateVestingTemplate({
+        cliffDuration: new anchor.BN(0),
+        vestingDuration: new anchor.BN(24 * MONTH),
+        tgeUnlockPercentage: 0,
+        curve: { periodicStep: { period: new anchor.BN(MONTH) } },
+      })
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        vestingTemplate: buybackTemplate,
+      })
+      .rpc();
+
+    const templateAccount = await program.account.vestingTemplate.fetch(
+      buybackTemplate
+    );
+    expect(templateAccount.schedule.curve.periodicStep.period.eq(new anchor.BN(MONTH)))
+      .to.be.true;
+
+    // Unlock tables must end fully unlocked
+    const now = Math.floor(Date.now() / 1000);
+    try {
+      await program.methods
+        .updateVestingTemplate({
+          cliffDuration: new anchor.BN(0),
+          vestingDuration: new anchor.BN(0),
+          tgeUnlockPercentage: 0,
+          curve: {
+            table: {
+              entries: [
+                { timestamp: new anchor.BN(now + MONTH), cumulativeBps: 2500 },
+                { timestamp: new anchor.BN(now + 6 * MONTH), cumulativeBps: 7500 },
+              ],
+            },
+          },
+        })
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          vestingTemplate: buybackTemplate,
+        })
+        .rpc();
+      expect.fail("Expected InvalidVestingSchedule");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidVestingSchedule");
+    }
+  });
+
+  it("Set the global TGE timestamp", async () => {
+    console.log("\n=== Testing TGE Timestamp ===");
+
+    // TGE just passed, so TGE unlocks are paid out when allocations are created
+    const tgeTimestamp = new anchor.BN(Math.floor(Date.now() / 1000) - 60);
+
+    await program.methods
+      .setTgeTimestamp(tgeTimestamp)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+      })
+      .rpc();
+
+    const tokenDataAccount = await program.account.tokenData.fetch(tokenData);
+    expect(tokenDataAccount.tgeTimestamp.eq(tgeTimestamp)).to.be.true;
+
+    // TGE can only be set once
+    try {
+      await program.methods
+        .setTgeTimestamp(tgeTimestamp.add(new anchor.BN(MONTH)))
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+        })
+        .rpc();
+      expect.fail("Expected TgeAlreadySet");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("TgeAlreadySet");
+    }
+  });
+
   it("Mint tokens for Token Sale allocation", async () => {
     console.log("\n=== Testing Token Sale Allocation ===");
 
@@ -127,10 +380,7 @@ describe("vtr-token", () => {
     );
     await new Promise((resolve) => setTimeout(resolve, 1000));
 
-    const [allocation] = await PublicKey.findProgramAddress(
-      [Buffer.from("allocation"), recipient.publicKey.toBuffer()],
-      program.programId
-    );
+    const allocation = await findNextAllocation();
 
     const recipientTokenAccount = await getAssociatedTokenAddress(
       mint.publicKey,
@@ -138,13 +388,17 @@ describe("vtr-token", () => {
     );
 
     const tx = await program.methods
-      .mintTokens(amount, { tokenSale: {} })
+      .mintTokens(amount, { tokenSale: {} }, false, [])
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
+        allocationBudget,
         recipient: recipient.publicKey,
         recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(recipient.publicKey),
+        vestingTemplate: findVestingTemplate(TOKEN_SALE),
+        allocationVault: null,
         allocation,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -163,12 +417,12 @@ describe("vtr-token", () => {
       recipient: allocationAccount.recipient.toString(),
       amount: allocationAccount.amount.toString(),
       allocationType: allocationAccount.allocationType,
-      tgeUnlockPercentage: allocationAccount.tgeUnlockPercentage,
+      tgeUnlockPercentage: allocationAccount.schedule.tgeUnlockPercentage,
       claimedAmount: allocationAccount.claimedAmount.toString(),
     });
 
     expect(allocationAccount.amount.eq(amount)).to.be.true;
-    expect(allocationAccount.tgeUnlockPercentage).to.equal(1000); // 10%
+    expect(allocationAccount.schedule.tgeUnlockPercentage).to.equal(1000); // 10%
     expect(allocationAccount.recipient.equals(recipient.publicKey)).to.be.true;
 
     // Verify TGE unlock (10% should be immediately available)
@@ -189,6 +443,87 @@ describe("vtr-token", () => {
     // Verify updated token data
     const tokenDataAccount = await program.account.tokenData.fetch(tokenData);
     expect(tokenDataAccount.circulatingSupply.eq(expectedTgeAmount)).to.be.true;
+    // The full allocation is reserved, not just the TGE unlock
+    expect(tokenDataAccount.allocatedSupply.eq(amount)).to.be.true;
+  });
+
+  it("Reject allocations beyond the total supply", async () => {
+    console.log("\n=== Testing Over-Allocation ===");
+
+    const recipient = Keypair.generate();
+    const tokenDataAccount = await program.account.tokenData.fetch(tokenData);
+    const unallocated = tokenDataAccount.totalSupply.sub(
+      tokenDataAccount.allocatedSupply
+    );
+
+    try {
+      await program.methods
+        .mintTokens(unallocated.add(new anchor.BN(1)), { teamAdvisors: {} }, true, [])
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          recipient: recipient.publicKey,
+          recipientTokenAccount: await getAssociatedTokenAddress(
+            mint.publicKey,
+            recipient.publicKey
+          ),
+          allocationRegistry: findAllocationRegistry(recipient.publicKey),
+          allocation: await findNextAllocation(),
+          vestingTemplate: findVestingTemplate(TEAM_ADVISORS),
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+      expect.fail("Expected OverAllocation");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("OverAllocation");
+    }
+  });
+
+  it("Enforce per-category budgets", async () => {
+    console.log("\n=== Testing Category Budgets ===");
+
+    // The token sale budget was used up by the first allocation
+    const budgetAccount = await program.account.allocationBudget.fetch(
+      allocationBudget
+    );
+    expect(budgetAccount.caps[TOKEN_SALE].eq(TOKEN_SALE_ALLOCATION)).to.be.true;
+    expect(budgetAccount.allocated[TOKEN_SALE].eq(TOKEN_SALE_ALLOCATION)).to.be
+      .true;
+
+    const recipient = Keypair.generate();
+    try {
+      await program.methods
+        .mintTokens(new anchor.BN(10 ** 9), { tokenSale: {} }, false, [])
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          recipient: recipient.publicKey,
+          recipientTokenAccount: await getAssociatedTokenAddress(
+            mint.publicKey,
+            recipient.publicKey
+          ),
+          allocationRegistry: findAllocationRegistry(recipient.publicKey),
+          allocation: await findNextAllocation(),
+          vestingTemplate: findVestingTemplate(TOKEN_SALE),
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+      expect.fail("Expected CategoryBudgetExceeded");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("CategoryBudgetExceeded");
+    }
   });
 
   it("Test Team & Advisors allocation (0% TGE)", async () => {
@@ -204,10 +539,7 @@ describe("vtr-token", () => {
     );
     await new Promise((resolve) => setTimeout(resolve, 1000));
 
-    const [allocation] = await PublicKey.findProgramAddress(
-      [Buffer.from("allocation"), teamRecipient.publicKey.toBuffer()],
-      program.programId
-    );
+    const allocation = await findNextAllocation();
 
     const recipientTokenAccount = await getAssociatedTokenAddress(
       mint.publicKey,
@@ -215,13 +547,17 @@ describe("vtr-token", () => {
     );
 
     const tx = await program.methods
-      .mintTokens(amount, { teamAdvisors: {} })
+      .mintTokens(amount, { teamAdvisors: {} }, true, [])
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
+        allocationBudget,
         recipient: teamRecipient.publicKey,
         recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(teamRecipient.publicKey),
+        vestingTemplate: findVestingTemplate(TEAM_ADVISORS),
+        allocationVault: null,
         allocation,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -237,9 +573,21 @@ describe("vtr-token", () => {
       allocation
     );
     expect(allocationAccount.amount.eq(amount)).to.be.true;
-    expect(allocationAccount.tgeUnlockPercentage).to.equal(0); // 0% TGE
+    expect(allocationAccount.schedule.tgeUnlockPercentage).to.equal(0); // 0% TGE
     expect(allocationAccount.claimedAmount.eq(new anchor.BN(0))).to.be.true; // No immediate unlock
 
+    // The preview view reports the cliff without reimplementing the vesting math
+    const { tgeTimestamp } = await program.account.tokenData.fetch(tokenData);
+    const preview = await program.methods
+      .previewAllocation()
+      .accountsPartial({ mint: mint.publicKey, tokenData, allocation })
+      .view();
+    const vestingEnd = tgeTimestamp.addn(12 * MONTH + 36 * MONTH);
+    expect(preview.vestedAmount.eq(new anchor.BN(0))).to.be.true;
+    expect(preview.claimableAmount.eq(new anchor.BN(0))).to.be.true;
+    expect(preview.nextUnlockTimestamp.eq(tgeTimestamp.addn(12 * MONTH + 1))).to.be.true;
+    expect(preview.fullyVestedTimestamp.eq(vestingEnd)).to.be.true;
+
     console.log(
       "✅ Team allocation created with 12-month cliff and 36-month vesting"
     );
@@ -258,10 +606,7 @@ describe("vtr-token", () => {
     );
     await new Promise((resolve) => setTimeout(resolve, 1000));
 
-    const [allocation] = await PublicKey.findProgramAddress(
-      [Buffer.from("allocation"), liquidityRecipient.publicKey.toBuffer()],
-      program.programId
-    );
+    const allocation = await findNextAllocation();
 
     const recipientTokenAccount = await getAssociatedTokenAddress(
       mint.publicKey,
@@ -269,13 +614,17 @@ describe("vtr-token", () => {
     );
 
     const tx = await program.methods
-      .mintTokens(amount, { liquidity: {} })
+      .mintTokens(amount, { liquidity: {} }, false, [])
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
+        allocationBudget,
         recipient: liquidityRecipient.publicKey,
         recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(liquidityRecipient.publicKey),
+        vestingTemplate: findVestingTemplate(LIQUIDITY),
+        allocationVault: null,
         allocation,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
@@ -290,7 +639,7 @@ describe("vtr-token", () => {
     const allocationAccount = await program.account.tokenAllocation.fetch(
       allocation
     );
-    expect(allocationAccount.tgeUnlockPercentage).to.equal(5000); // 50% TGE
+    expect(allocationAccount.schedule.tgeUnlockPercentage).to.equal(5000); // 50% TGE
 
     // Verify 50% immediate unlock
     const expectedTgeAmount = amount
@@ -310,176 +659,244 @@ describe("vtr-token", () => {
     console.log("✅ Liquidity allocation created with 50% immediate unlock");
   });
 
-  it("Initialize staking pool", async () => {
-    console.log("\n=== Testing Staking Pool Initialization ===");
+  it("Escrow an allocation in a vault", async () => {
+    console.log("\n=== Testing Escrowed Allocation ===");
 
-    const apyPercentage = 1500; // 15% APY (from tokenomics: 5-15% APY)
-    const minStakeDuration = new anchor.BN(30 * 24 * 3600); // 30 days in seconds
+    const grantee = Keypair.generate();
+    const amount = new anchor.BN(10_000_000 * 10 ** 9); // 10M tokens
+    const allocation = await findNextAllocation();
+    const allocationVault = findAllocationVault(allocation);
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      grantee.publicKey
+    );
 
-    const tx = await program.methods
-      .initializeStaking(apyPercentage, minStakeDuration)
+    const tokenDataBefore = await program.account.tokenData.fetch(tokenData);
+    const mintBefore = await getMint(provider.connection, mint.publicKey);
+
+    await program.methods
+      .mintTokens(amount, { ecosystemGrowth: {} }, false, [])
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
-        stakingPool,
-        stakingVault,
+        tokenData,
+        allocationBudget,
+        recipient: grantee.publicKey,
+        recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(grantee.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(ECOSYSTEM_GROWTH),
+        allocationVault,
         tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
         rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
       .rpc();
 
-    console.log("Staking initialization transaction:", tx);
+    // 15% is paid at TGE, the rest is locked in the vault
+    const tgeAmount = amount.mul(new anchor.BN(1500)).div(new anchor.BN(10000));
+    const lockedAmount = amount.sub(tgeAmount);
 
-    // Verify staking pool
-    const stakingPoolAccount = await program.account.stakingPool.fetch(
-      stakingPool
+    const allocationAccount = await program.account.tokenAllocation.fetch(
+      allocation
     );
-    console.log("Staking Pool:", {
-      authority: stakingPoolAccount.authority.toString(),
-      apyPercentage: stakingPoolAccount.apyPercentage,
-      minStakeDuration: stakingPoolAccount.minStakeDuration.toString(),
-      totalStaked: stakingPoolAccount.totalStaked.toString(),
-    });
+    expect(allocationAccount.escrowed).to.be.true;
 
-    expect(stakingPoolAccount.apyPercentage).to.equal(apyPercentage);
+    const vault = await getAccount(provider.connection, allocationVault);
+    expect(vault.amount.toString()).to.equal(lockedAmount.toString());
+    expect(vault.owner.equals(tokenData)).to.be.true;
+
+    // The whole allocation shows up in the SPL mint supply
+    const mintAfter = await getMint(provider.connection, mint.publicKey);
+    expect((mintAfter.supply - mintBefore.supply).toString()).to.equal(
+      amount.toString()
+    );
+
+    const tokenDataAfter = await program.account.tokenData.fetch(tokenData);
     expect(
-      stakingPoolAccount.minStakeDuration.eq(new anchor.BN(minStakeDuration))
+      tokenDataAfter.lockedSupply.eq(tokenDataBefore.lockedSupply.add(lockedAmount))
+    ).to.be.true;
+    expect(
+      tokenDataAfter.circulatingSupply.eq(
+        tokenDataBefore.circulatingSupply.add(tgeAmount)
+      )
     ).to.be.true;
-    expect(stakingPoolAccount.totalStaked.eq(new anchor.BN(0))).to.be.true;
-    expect(stakingPoolAccount.authority.equals(authority.publicKey)).to.be.true;
-
-    // Verify staking vault exists
-    const vault = await getAccount(provider.connection, stakingVault);
-    expect(vault.amount.toString()).to.equal("0");
-    expect(vault.mint.equals(mint.publicKey)).to.be.true;
   });
 
-  it("Stake tokens", async () => {
-    console.log("\n=== Testing Token Staking ===");
-
-    const user = Keypair.generate();
-    const stakeAmount = new anchor.BN(100_000 * 10 ** 9); // 100K tokens
-    const stakeDuration = new anchor.BN(90 * 24 * 3600); // 90 days (minimum is 30 days)
+  it("Hold multiple allocations per recipient", async () => {
+    console.log("\n=== Testing Multiple Allocations Per Recipient ===");
 
-    // Airdrop SOL to user
-    await provider.connection.requestAirdrop(
-      user.publicKey,
-      2 * LAMPORTS_PER_SOL
+    const advisor = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      advisor.publicKey
     );
-    await new Promise((resolve) => setTimeout(resolve, 1000));
-
-    // First, mint some tokens to user using Liquidity allocation for instant unlock
-    const [userAllocation] = await PublicKey.findProgramAddress(
-      [Buffer.from("allocation"), user.publicKey.toBuffer()],
-      program.programId
+    const allocationRegistry = findAllocationRegistry(advisor.publicKey);
+
+    // A contributor holding a reserve grant and a marketing grant
+    const grants = [
+      {
+        allocationType: { platformReserve: {} },
+        template: PLATFORM_RESERVE,
+        revocable: true,
+      },
+      { allocationType: { marketing: {} }, template: MARKETING, revocable: false },
+    ];
+    const allocations: PublicKey[] = [];
+    for (const grant of grants) {
+      const allocation = await findNextAllocation();
+      allocations.push(allocation);
+
+      await program.methods
+        .mintTokens(amount, grant.allocationType, grant.revocable, [])
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          recipient: advisor.publicKey,
+          recipientTokenAccount,
+          allocationRegistry,
+          allocation,
+          vestingTemplate: findVestingTemplate(grant.template),
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+    }
+
+    const registryAccount = await program.account.allocationRegistry.fetch(
+      allocationRegistry
     );
+    expect(registryAccount.allocations.length).to.equal(2);
+    expect(registryAccount.allocations[0].equals(allocations[0])).to.be.true;
+    expect(registryAccount.allocations[1].equals(allocations[1])).to.be.true;
 
-    const userTokenAccount = await getAssociatedTokenAddress(
-      mint.publicKey,
-      user.publicKey
+    const [reserveGrant, marketingGrant] = await Promise.all(
+      allocations.map((allocation) =>
+        program.account.tokenAllocation.fetch(allocation)
+      )
     );
+    expect(reserveGrant.allocationType).to.deep.equal({ platformReserve: {} });
+    expect(marketingGrant.allocationType).to.deep.equal({ marketing: {} });
+    expect(marketingGrant.id.eq(reserveGrant.id.add(new anchor.BN(1)))).to.be
+      .true;
 
-    // Mint tokens to user
+    // The marketing grant was created irrevocable
+    try {
+      await program.methods
+        .revokeAllocation()
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation: allocations[1],
+          allocationVault: null,
+          treasuryTokenAccount: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      expect.fail("Expected AllocationNotRevocable");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("AllocationNotRevocable");
+    }
+
+    // The reserve grant is still inside its cliff, so only the TGE unlock is kept
     await program.methods
-      .mintTokens(stakeAmount.mul(new anchor.BN(2)), { liquidity: {} }) // Mint 2x stake amount
+      .revokeAllocation()
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
-        recipient: user.publicKey,
-        recipientTokenAccount: userTokenAccount,
-        allocation: userAllocation,
+        allocationBudget,
+        allocation: allocations[0],
+        allocationVault: null,
+        treasuryTokenAccount: null,
         tokenProgram: TOKEN_PROGRAM_ID,
-        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
-        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
       .rpc();
 
-    console.log("Tokens minted to user for staking");
-
-    // Verify user has tokens
-    const userBalance = await getAccount(provider.connection, userTokenAccount);
-    console.log("User balance before staking:", userBalance.amount.toString());
-
-    // Now stake tokens
-    const [stakeAccount] = await PublicKey.findProgramAddress(
-      [Buffer.from("stake_account"), user.publicKey.toBuffer()],
-      program.programId
+    const revokedGrant = await program.account.tokenAllocation.fetch(
+      allocations[0]
     );
-
-    const tx = await program.methods
-      .stakeTokens(stakeAmount, stakeDuration)
+    expect(revokedGrant.revoked).to.be.true;
+    expect(revokedGrant.revokedAt.gt(new anchor.BN(0))).to.be.true;
+    expect(revokedGrant.amount.eq(revokedGrant.claimedAmount)).to.be.true;
+    expect(revokedGrant.payer.equals(authority.publicKey)).to.be.true;
+
+    // The marketing grant still has tokens to vest, so it can't be closed
+    try {
+      await program.methods
+        .closeAllocation()
+        .accountsPartial({
+          payer: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationRegistry,
+          allocation: allocations[1],
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      expect.fail("Expected AllocationNotSettled");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("AllocationNotSettled");
+    }
+
+    // The settled reserve grant is closed and its rent goes back to the payer
+    await program.methods
+      .closeAllocation()
       .accountsPartial({
-        user: user.publicKey,
+        payer: authority.publicKey,
         mint: mint.publicKey,
-        stakingPool,
-        userTokenAccount,
-        stakingVault,
-        stakeAccount,
+        tokenData,
+        allocationRegistry,
+        allocation: allocations[0],
+        allocationVault: null,
         tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
-        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
-      .signers([user])
       .rpc();
 
-    console.log("Staking transaction:", tx);
-
-    // Verify stake account
-    const stakeAccountData = await program.account.stakeAccount.fetch(
-      stakeAccount
-    );
-    console.log("Stake Account:", {
-      user: stakeAccountData.user.toString(),
-      amount: stakeAccountData.amount.toString(),
-      stakeTime: stakeAccountData.stakeTime.toString(),
-      unlockTime: stakeAccountData.unlockTime.toString(),
-    });
-
-    expect(stakeAccountData.amount.eq(stakeAmount)).to.be.true;
-    expect(stakeAccountData.user.equals(user.publicKey)).to.be.true;
-    expect(stakeAccountData.unlockTime.gt(stakeAccountData.stakeTime)).to.be
-      .true;
-
-    // Verify staking pool updated
-    const stakingPoolAccount = await program.account.stakingPool.fetch(
-      stakingPool
+    expect(await provider.connection.getAccountInfo(allocations[0])).to.be.null;
+    const registryAfterClose = await program.account.allocationRegistry.fetch(
+      allocationRegistry
     );
-    expect(stakingPoolAccount.totalStaked.eq(stakeAmount)).to.be.true;
-
-    // Verify tokens moved to staking vault
-    const vaultBalance = await getAccount(provider.connection, stakingVault);
-    expect(vaultBalance.amount.toString()).to.equal(stakeAmount.toString());
+    expect(registryAfterClose.allocations.length).to.equal(1);
+    expect(registryAfterClose.allocations[0].equals(allocations[1])).to.be.true;
   });
 
-  it("Burn tokens", async () => {
-    console.log("\n=== Testing Token Burning ===");
-
-    const burnAmount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
-
-    // First, mint tokens to authority for burning using Marketing allocation (20% TGE)
-    const [authorityAllocation] = await PublicKey.findProgramAddress(
-      [Buffer.from("allocation"), authority.publicKey.toBuffer()],
-      program.programId
-    );
+  it("Close an escrowed allocation with stray vault tokens", async () => {
+    console.log("\n=== Testing Escrowed Allocation Close ===");
 
-    const authorityTokenAccount = await getAssociatedTokenAddress(
+    const grantee = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const allocationVault = findAllocationVault(allocation);
+    const allocationRegistry = findAllocationRegistry(grantee.publicKey);
+    const recipientTokenAccount = await getAssociatedTokenAddress(
       mint.publicKey,
-      authority.publicKey
+      grantee.publicKey
     );
 
-    // Mint tokens for burning using Marketing allocation
     await program.methods
-      .mintTokens(burnAmount.mul(new anchor.BN(5)), { marketing: {} }) // Mint 5x burn amount
+      .mintTokens(amount, { platformReserve: {} }, true, [])
       .accountsPartial({
         authority: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
-        recipient: authority.publicKey,
-        recipientTokenAccount: authorityTokenAccount,
-        allocation: authorityAllocation,
+        allocationBudget,
+        recipient: grantee.publicKey,
+        recipientTokenAccount,
+        allocationRegistry,
+        allocation,
+        vestingTemplate: findVestingTemplate(PLATFORM_RESERVE),
+        allocationVault,
         tokenProgram: TOKEN_PROGRAM_ID,
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
@@ -487,67 +904,1791 @@ describe("vtr-token", () => {
       })
       .rpc();
 
-    console.log("Tokens minted for burning");
+    // Revoking inside the cliff burns the locked remainder and settles the grant
+    await program.methods
+      .revokeAllocation()
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        allocation,
+        allocationVault,
+        treasuryTokenAccount: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
 
-    // Check balance before burning
-    const balanceBefore = await getAccount(
+    // Someone sends a base unit to the vault to keep it from being closed
+    await transfer(
       provider.connection,
-      authorityTokenAccount
+      authority.payer,
+      recipientTokenAccount,
+      allocationVault,
+      grantee,
+      1
     );
-    console.log("Balance before burn:", balanceBefore.amount.toString());
 
-    // Get token data before burning
     const tokenDataBefore = await program.account.tokenData.fetch(tokenData);
-    console.log(
-      "Before burn - Burned supply:",
-      tokenDataBefore.burnedSupply.toString()
-    );
-    console.log(
-      "Before burn - Circulating supply:",
-      tokenDataBefore.circulatingSupply.toString()
-    );
-
-    // Burn tokens
-    const tx = await program.methods
-      .burnTokens(burnAmount)
+    await program.methods
+      .closeAllocation()
       .accountsPartial({
-        authority: authority.publicKey,
+        payer: authority.publicKey,
         mint: mint.publicKey,
         tokenData,
-        fromTokenAccount: authorityTokenAccount,
-        burnVault,
+        allocationRegistry,
+        allocation,
+        allocationVault,
         tokenProgram: TOKEN_PROGRAM_ID,
-        systemProgram: SystemProgram.programId,
-        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
       })
       .rpc();
 
-    console.log("Burn transaction:", tx);
-
-    // Verify burn
+    // The stray unit is burned and both accounts are closed
+    expect(await provider.connection.getAccountInfo(allocation)).to.be.null;
+    expect(await provider.connection.getAccountInfo(allocationVault)).to.be.null;
     const tokenDataAfter = await program.account.tokenData.fetch(tokenData);
-    console.log(
-      "After burn - Burned supply:",
-      tokenDataAfter.burnedSupply.toString()
-    );
-    console.log(
-      "After burn - Circulating supply:",
-      tokenDataAfter.circulatingSupply.toString()
-    );
-
-    expect(tokenDataAfter.burnedSupply.eq(burnAmount)).to.be.true;
     expect(
-      tokenDataAfter.circulatingSupply.eq(
-        tokenDataBefore.circulatingSupply.sub(burnAmount)
-      )
+      tokenDataAfter.burnedSupply.sub(tokenDataBefore.burnedSupply).eq(new anchor.BN(1))
     ).to.be.true;
-
-    // Verify tokens are in burn vault
-    const burnVaultBalance = await getAccount(provider.connection, burnVault);
-    expect(burnVaultBalance.amount.toString()).to.equal(burnAmount.toString());
   });
 
-  it("Display final token statistics", async () => {
+  it("Transfer an allocation to a new beneficiary", async () => {
+    console.log("\n=== Testing Beneficiary Transfer ===");
+
+    const oldWallet = Keypair.generate();
+    const newWallet = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const marketingTemplate = findVestingTemplate(MARKETING);
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: oldWallet.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          oldWallet.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(oldWallet.publicKey),
+        allocation,
+        vestingTemplate: marketingTemplate,
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    // Marketing grants need the authority to co-sign beneficiary changes
+    await program.methods
+      .setBeneficiaryChangePolicy(true)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        vestingTemplate: marketingTemplate,
+      })
+      .rpc();
+
+    const proposeAccounts = {
+      recipient: oldWallet.publicKey,
+      mint: mint.publicKey,
+      tokenData,
+      vestingTemplate: marketingTemplate,
+      allocation,
+    };
+    try {
+      await program.methods
+        .proposeBeneficiaryChange(newWallet.publicKey)
+        .accountsPartial({ ...proposeAccounts, authority: null })
+        .signers([oldWallet])
+        .rpc();
+      expect.fail("Expected AuthorityCosignRequired");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("AuthorityCosignRequired");
+    }
+
+    // Re-proposing the current recipient would list the allocation twice in its registry
+    try {
+      await program.methods
+        .proposeBeneficiaryChange(oldWallet.publicKey)
+        .accountsPartial({ ...proposeAccounts, authority: authority.publicKey })
+        .signers([oldWallet])
+        .rpc();
+      expect.fail("Expected SameBeneficiary");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("SameBeneficiary");
+    }
+
+    await program.methods
+      .proposeBeneficiaryChange(newWallet.publicKey)
+      .accountsPartial({ ...proposeAccounts, authority: authority.publicKey })
+      .signers([oldWallet])
+      .rpc();
+
+    await provider.connection.requestAirdrop(newWallet.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    await program.methods
+      .acceptBeneficiaryChange()
+      .accountsPartial({
+        newRecipient: newWallet.publicKey,
+        mint: mint.publicKey,
+        allocation,
+        currentRegistry: findAllocationRegistry(oldWallet.publicKey),
+        newRegistry: findAllocationRegistry(newWallet.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([newWallet])
+      .rpc();
+
+    // The allocation keeps its address and moves between registries
+    const allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.recipient.equals(newWallet.publicKey)).to.be.true;
+    expect(allocationAccount.pendingRecipient.equals(PublicKey.default)).to.be.true;
+
+    const oldRegistry = await program.account.allocationRegistry.fetch(
+      findAllocationRegistry(oldWallet.publicKey)
+    );
+    const newRegistry = await program.account.allocationRegistry.fetch(
+      findAllocationRegistry(newWallet.publicKey)
+    );
+    expect(oldRegistry.allocations.length).to.equal(0);
+    expect(newRegistry.allocations[0].equals(allocation)).to.be.true;
+
+    await program.methods
+      .setBeneficiaryChangePolicy(false)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        vestingTemplate: marketingTemplate,
+      })
+      .rpc();
+  });
+
+  it("Recover an allocation through guardians", async () => {
+    console.log("\n=== Testing Lost-Key Recovery ===");
+
+    const contributor = Keypair.generate();
+    const newWallet = Keypair.generate();
+    const guardians = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+
+    for (const wallet of [contributor, newWallet]) {
+      await provider.connection.requestAirdrop(wallet.publicKey, LAMPORTS_PER_SOL);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: contributor.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          contributor.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(contributor.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    const [recovery] = PublicKey.findProgramAddressSync(
+      [Buffer.from("allocation_recovery"), allocation.toBuffer()],
+      program.programId
+    );
+
+    const configureRecovery = (recoveryDelay: number) =>
+      program.methods
+        .configureRecovery(
+          guardians.map((guardian) => guardian.publicKey),
+          2,
+          new anchor.BN(recoveryDelay)
+        )
+        .accountsPartial({
+          recipient: contributor.publicKey,
+          mint: mint.publicKey,
+          allocation,
+          recovery,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([contributor])
+        .rpc();
+
+    // Without a waiting period the current key could never cancel
+    try {
+      await configureRecovery(0);
+      expect.fail("Expected InvalidRecoveryConfig");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidRecoveryConfig");
+    }
+
+    // Two of three guardians with a one-day waiting period
+    await configureRecovery(24 * 3600);
+
+    const approve = (guardian: Keypair) =>
+      program.methods
+        .approveRecovery(newWallet.publicKey)
+        .accountsPartial({ guardian: guardian.publicKey, allocation, recovery })
+        .signers([guardian])
+        .rpc();
+    const executeRecovery = () =>
+      program.methods
+        .executeRecovery()
+        .accountsPartial({
+          newRecipient: newWallet.publicKey,
+          mint: mint.publicKey,
+          allocation,
+          recovery,
+          currentRegistry: findAllocationRegistry(contributor.publicKey),
+          newRegistry: findAllocationRegistry(newWallet.publicKey),
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([newWallet])
+        .rpc();
+
+    await approve(guardians[0]);
+    try {
+      await executeRecovery();
+      expect.fail("Expected RecoveryNotReady");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RecoveryNotReady");
+    }
+
+    // The original key can still veto once the threshold is met
+    await approve(guardians[1]);
+    await program.methods
+      .cancelRecovery()
+      .accountsPartial({ recipient: contributor.publicKey, allocation, recovery })
+      .signers([contributor])
+      .rpc();
+    let recoveryAccount = await program.account.allocationRecovery.fetch(recovery);
+    expect(recoveryAccount.approvals.every((approved) => approved.equals(PublicKey.default)))
+      .to.be.true;
+    expect(recoveryAccount.thresholdMetAt.toNumber()).to.equal(0);
+
+    // A guardian backing another key keeps the others' approvals intact
+    const otherWallet = Keypair.generate();
+    const approveOther = () =>
+      program.methods
+        .approveRecovery(otherWallet.publicKey)
+        .accountsPartial({ guardian: guardians[0].publicKey, allocation, recovery })
+        .signers([guardians[0]])
+        .rpc();
+    await approve(guardians[1]);
+    await approveOther();
+    await approve(guardians[2]);
+    recoveryAccount = await program.account.allocationRecovery.fetch(recovery);
+    expect(recoveryAccount.pendingRecipient.equals(newWallet.publicKey)).to.be.true;
+    expect(recoveryAccount.thresholdMetAt.gt(new anchor.BN(0))).to.be.true;
+
+    // Nor can a single guardian restart the waiting period once it runs
+    try {
+      await approveOther();
+      expect.fail("Expected RecoveryInProgress");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RecoveryInProgress");
+    }
+
+    // Meeting the threshold only starts the waiting period
+    try {
+      await executeRecovery();
+      expect.fail("Expected RecoveryNotReady");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RecoveryNotReady");
+    }
+    const allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.recipient.equals(contributor.publicKey)).to.be.true;
+  });
+
+  it("Drop guardians when an allocation changes hands", async () => {
+    console.log("\n=== Testing Recovery After Transfer ===");
+
+    const seller = Keypair.generate();
+    const sellerBackupKey = Keypair.generate();
+    const buyer = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+
+    for (const wallet of [seller, buyer]) {
+      await provider.connection.requestAirdrop(wallet.publicKey, LAMPORTS_PER_SOL);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: seller.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          seller.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(seller.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    const [recovery] = PublicKey.findProgramAddressSync(
+      [Buffer.from("allocation_recovery"), allocation.toBuffer()],
+      program.programId
+    );
+
+    // The seller makes their own second key the only guardian before selling
+    await program.methods
+      .configureRecovery([sellerBackupKey.publicKey], 1, new anchor.BN(24 * 3600))
+      .accountsPartial({
+        recipient: seller.publicKey,
+        mint: mint.publicKey,
+        allocation,
+        recovery,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([seller])
+      .rpc();
+
+    await program.methods
+      .proposeBeneficiaryChange(buyer.publicKey)
+      .accountsPartial({
+        recipient: seller.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocation,
+        authority: null,
+      })
+      .signers([seller])
+      .rpc();
+    await program.methods
+      .acceptBeneficiaryChange()
+      .accountsPartial({
+        newRecipient: buyer.publicKey,
+        mint: mint.publicKey,
+        allocation,
+        currentRegistry: findAllocationRegistry(seller.publicKey),
+        newRegistry: findAllocationRegistry(buyer.publicKey),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+
+    // The seller's guardian can no longer start a recovery for the buyer's allocation
+    try {
+      await program.methods
+        .approveRecovery(seller.publicKey)
+        .accountsPartial({ guardian: sellerBackupKey.publicKey, allocation, recovery })
+        .signers([sellerBackupKey])
+        .rpc();
+      expect.fail("Expected RecoveryConfigStale");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("RecoveryConfigStale");
+    }
+
+    // The buyer sets up their own guardians on the same account
+    const buyerGuardian = Keypair.generate();
+    await program.methods
+      .configureRecovery([buyerGuardian.publicKey], 1, new anchor.BN(24 * 3600))
+      .accountsPartial({
+        recipient: buyer.publicKey,
+        mint: mint.publicKey,
+        allocation,
+        recovery,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([buyer])
+      .rpc();
+    const recoveryAccount = await program.account.allocationRecovery.fetch(recovery);
+    expect(recoveryAccount.recipient.equals(buyer.publicKey)).to.be.true;
+    expect(recoveryAccount.guardians.length).to.equal(1);
+    expect(recoveryAccount.guardians[0].equals(buyerGuardian.publicKey)).to.be.true;
+  });
+
+  it("Unlock milestone tranches on attestation", async () => {
+    console.log("\n=== Testing Milestone Vesting ===");
+
+    const grantee = Keypair.generate();
+    const attestor = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+
+    // 30% on each of two milestones, the remaining 40% on the ecosystem schedule
+    const milestones = [
+      { trancheBps: 3000, attestor: attestor.publicKey },
+      { trancheBps: 3000, attestor: attestor.publicKey },
+    ];
+    await program.methods
+      .mintTokens(amount, { ecosystemGrowth: {} }, false, milestones)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: grantee.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          grantee.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(grantee.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(ECOSYSTEM_GROWTH),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    // The 15% TGE unlock only applies to the time-based 40%
+    const timeAmount = amount.muln(4000).divn(10000);
+    const trancheAmount = amount.muln(3000).divn(10000);
+    let allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.claimedAmount.eq(timeAmount.muln(1500).divn(10000))).to
+      .be.true;
+    expect(allocationAccount.milestones.length).to.equal(2);
+
+    try {
+      await program.methods
+        .attestMilestone(0)
+        .accountsPartial({ attestor: authority.publicKey, mint: mint.publicKey, allocation })
+        .rpc();
+      expect.fail("Expected InvalidMilestoneAttestor");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidMilestoneAttestor");
+    }
+
+    await program.methods
+      .attestMilestone(0)
+      .accountsPartial({ attestor: attestor.publicKey, mint: mint.publicKey, allocation })
+      .signers([attestor])
+      .rpc();
+
+    allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.milestones[0].attestedAt.gt(new anchor.BN(0))).to.be.true;
+    expect(allocationAccount.milestones[1].attestedAt.toNumber()).to.equal(0);
+
+    // The attested tranche is claimable on top of the schedule; full vesting waits on the other
+    const preview = await program.methods
+      .previewAllocation()
+      .accountsPartial({ mint: mint.publicKey, tokenData, allocation })
+      .view();
+    expect(preview.claimableAmount.gte(trancheAmount)).to.be.true;
+    expect(preview.fullyVestedTimestamp.toNumber()).to.equal(0);
+
+    const amend = (newAmount: anchor.BN) =>
+      program.methods
+        .amendAllocation({ changeAmount: { newAmount } })
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation,
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    // Halving the grant would eat into the tranches, including the attested one
+    try {
+      await amend(amount.divn(2));
+      expect.fail("Expected InvalidAmendment");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidAmendment");
+    }
+
+    // A cut within the time-based part leaves the attested tranche whole
+    await amend(amount.muln(8).divn(10));
+    allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.milestones[0].trancheAmount.eq(trancheAmount)).to.be.true;
+    expect(allocationAccount.milestones[1].trancheAmount.eq(trancheAmount)).to.be.true;
+    const amendedPreview = await program.methods
+      .previewAllocation()
+      .accountsPartial({ mint: mint.publicKey, tokenData, allocation })
+      .view();
+    expect(amendedPreview.vestedAmount.gte(preview.vestedAmount)).to.be.true;
+  });
+
+  it("Amend an allocation", async () => {
+    console.log("\n=== Testing Allocation Amendments ===");
+
+    const partner = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, true, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: partner.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          partner.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(partner.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    const amend = (amendment) =>
+      program.methods
+        .amendAllocation(amendment)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation,
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    // Extend by six months
+    await amend({ extendVesting: { additionalDuration: new anchor.BN(6 * MONTH) } });
+    let allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.schedule.vestingDuration.toNumber()).to.equal(24 * MONTH);
+
+    // A top-up is reserved against the supply like a new allocation
+    const tokenDataBefore = await program.account.tokenData.fetch(tokenData);
+    await amend({ changeAmount: { newAmount: amount.muln(2) } });
+    const tokenDataAfter = await program.account.tokenData.fetch(tokenData);
+    expect(
+      tokenDataAfter.allocatedSupply.sub(tokenDataBefore.allocatedSupply).eq(amount)
+    ).to.be.true;
+
+    // Cutting below what has already vested is rejected
+    try {
+      await amend({ changeAmount: { newAmount: new anchor.BN(1) } });
+      expect.fail("Expected InvalidAmendment");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidAmendment");
+    }
+
+    // Full acceleration, e.g. on change of control
+    await amend({ accelerate: { accelerationBps: 10000 } });
+    allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.amount.eq(amount.muln(2))).to.be.true;
+    expect(allocationAccount.vestedBase.eq(allocationAccount.amount)).to.be.true;
+    expect(allocationAccount.amendmentCount).to.equal(3);
+    expect(allocationAccount.lastAmendedAt.gt(new anchor.BN(0))).to.be.true;
+
+    const preview = await program.methods
+      .previewAllocation()
+      .accountsPartial({ mint: mint.publicKey, tokenData, allocation })
+      .view();
+    expect(preview.vestedAmount.eq(allocationAccount.amount)).to.be.true;
+    expect(preview.nextUnlockTimestamp.toNumber()).to.equal(0);
+  });
+
+  it("Tokenize an allocation into a position token", async () => {
+    console.log("\n=== Testing Position Tokens ===");
+
+    const investor = Keypair.generate();
+    const fund = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const marketingTemplate = findVestingTemplate(MARKETING);
+
+    await provider.connection.requestAirdrop(investor.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: investor.publicKey,
+        recipientTokenAccount: await getAssociatedTokenAddress(
+          mint.publicKey,
+          investor.publicKey
+        ),
+        allocationRegistry: findAllocationRegistry(investor.publicKey),
+        allocation,
+        vestingTemplate: marketingTemplate,
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    const [positionMint] = PublicKey.findProgramAddressSync(
+      [Buffer.from("position_mint"), allocation.toBuffer()],
+      program.programId
+    );
+    const investorPositionAccount = await getAssociatedTokenAddress(
+      positionMint,
+      investor.publicKey
+    );
+    const setPolicy = (allowed: boolean) =>
+      program.methods
+        .setPositionTokenPolicy(allowed)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          vestingTemplate: marketingTemplate,
+        })
+        .rpc();
+    const tokenize = () =>
+      program.methods
+        .tokenizeAllocation()
+        .accountsPartial({
+          recipient: investor.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          vestingTemplate: marketingTemplate,
+          allocation,
+          positionMint,
+          positionTokenAccount: investorPositionAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([investor])
+        .rpc();
+
+    // The authority can switch position tokens off per category, e.g. for team grants
+    await setPolicy(false);
+    try {
+      await tokenize();
+      expect.fail("Expected PositionTokensDisabled");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("PositionTokensDisabled");
+    }
+    await setPolicy(true);
+    await tokenize();
+
+    // A 1-of-1 token with no mint authority left
+    const positionMintAccount = await getMint(provider.connection, positionMint);
+    expect(positionMintAccount.decimals).to.equal(0);
+    expect(positionMintAccount.supply.toString()).to.equal("1");
+    expect(positionMintAccount.mintAuthority).to.be.null;
+
+    // Moving the position token moves the right to claim
+    const fundPositionAccount = await createAssociatedTokenAccount(
+      provider.connection,
+      authority.payer,
+      positionMint,
+      fund.publicKey
+    );
+    await transfer(
+      provider.connection,
+      authority.payer,
+      investorPositionAccount,
+      fundPositionAccount,
+      investor,
+      1
+    );
+    const fundTokenAccount = await createAssociatedTokenAccount(
+      provider.connection,
+      authority.payer,
+      mint.publicKey,
+      fund.publicKey
+    );
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    try {
+      await program.methods
+        .claimVestedTokens()
+        .accountsPartial({
+          recipient: investor.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation,
+          allocationVault: null,
+          positionTokenAccount: investorPositionAccount,
+          recipientTokenAccount: await getAssociatedTokenAddress(
+            mint.publicKey,
+            investor.publicKey
+          ),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([investor])
+        .rpc();
+      expect.fail("Expected NotBeneficiary");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("NotBeneficiary");
+    }
+
+    await program.methods
+      .claimVestedTokens()
+      .accountsPartial({
+        recipient: fund.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        allocation,
+        allocationVault: null,
+        positionTokenAccount: fundPositionAccount,
+        recipientTokenAccount: fundTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([fund])
+      .rpc();
+
+    const fundBalance = await getAccount(provider.connection, fundTokenAccount);
+    expect(fundBalance.amount > BigInt(0)).to.be.true;
+  });
+
+  it("Batch mint allocations", async () => {
+    console.log("\n=== Testing Batch Allocation Creation ===");
+
+    const buyers = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const amount = new anchor.BN(250_000 * 10 ** 9); // 250K tokens each
+    const { allocationCount } = await program.account.tokenData.fetch(tokenData);
+
+    const requests = buyers.map((buyer) => ({
+      recipient: buyer.publicKey,
+      amount,
+      allocationType: { ecosystemGrowth: {} },
+      revocable: false,
+      milestones: [],
+    }));
+
+    // Recipient, recipient token account, registry, allocation and template per request
+    const allocations = buyers.map((_, index) =>
+      findAllocation(allocationCount.addn(index))
+    );
+    const remainingAccounts = (
+      await Promise.all(
+        buyers.map(async (buyer, index) => [
+          { pubkey: buyer.publicKey, isSigner: false, isWritable: false },
+          {
+            pubkey: await getAssociatedTokenAddress(mint.publicKey, buyer.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+          {
+            pubkey: findAllocationRegistry(buyer.publicKey),
+            isSigner: false,
+            isWritable: true,
+          },
+          { pubkey: allocations[index], isSigner: false, isWritable: true },
+          {
+            pubkey: findVestingTemplate(ECOSYSTEM_GROWTH),
+            isSigner: false,
+            isWritable: false,
+          },
+        ])
+      )
+    ).flat();
+
+    await program.methods
+      .batchMintAllocations(requests)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(remainingAccounts)
+      .rpc();
+
+    // Every buyer got an allocation and their 15% TGE unlock
+    const expectedTgeAmount = amount
+      .mul(new anchor.BN(1500))
+      .div(new anchor.BN(10000));
+    for (const [index, buyer] of buyers.entries()) {
+      const allocationAccount = await program.account.tokenAllocation.fetch(
+        allocations[index]
+      );
+      expect(allocationAccount.recipient.equals(buyer.publicKey)).to.be.true;
+      expect(allocationAccount.claimedAmount.eq(expectedTgeAmount)).to.be.true;
+
+      const balance = await getAccount(
+        provider.connection,
+        await getAssociatedTokenAddress(mint.publicKey, buyer.publicKey)
+      );
+      expect(balance.amount.toString()).to.equal(expectedTgeAmount.toString());
+    }
+  });
+
+  it("Claim from a merkle distribution", async () => {
+    console.log("\n=== Testing Merkle Distributions ===");
+
+    const sha256 = (...parts: Buffer[]): Buffer =>
+      createHash("sha256").update(Buffer.concat(parts)).digest();
+    // Leaf and node hashes match the program's domain-separated, sorted-pair tree
+    const hashLeaf = (recipient: PublicKey, amount: anchor.BN): Buffer =>
+      sha256(
+        Buffer.from([0]),
+        recipient.toBuffer(),
+        amount.toArrayLike(Buffer, "le", 8),
+        Buffer.from([ECOSYSTEM_GROWTH])
+      );
+    const hashPair = (a: Buffer, b: Buffer): Buffer =>
+      Buffer.compare(a, b) <= 0
+        ? sha256(Buffer.from([1]), a, b)
+        : sha256(Buffer.from([1]), b, a);
+
+    const claimants = [Keypair.generate(), Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const amount = new anchor.BN(500_000 * 10 ** 9); // 500K tokens each
+    const totalAmount = amount.muln(claimants.length);
+    const leaves = claimants.map((claimant) => hashLeaf(claimant.publicKey, amount));
+    const left = hashPair(leaves[0], leaves[1]);
+    const right = hashPair(leaves[2], leaves[3]);
+    const merkleRoot = hashPair(left, right);
+
+    const [merkleDistribution] = PublicKey.findProgramAddressSync(
+      [Buffer.from("merkle_distribution"), mint.publicKey.toBuffer(), merkleRoot],
+      program.programId
+    );
+    const budgetBefore = await program.account.allocationBudget.fetch(allocationBudget);
+
+    await program.methods
+      .createMerkleDistribution([...merkleRoot], { ecosystemGrowth: {} }, totalAmount)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        vestingTemplate: findVestingTemplate(ECOSYSTEM_GROWTH),
+        merkleDistribution,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    // The distribution total is reserved against the category budget up front
+    const budgetAfter = await program.account.allocationBudget.fetch(allocationBudget);
+    expect(
+      budgetAfter.allocated[ECOSYSTEM_GROWTH]
+        .sub(budgetBefore.allocated[ECOSYSTEM_GROWTH])
+        .eq(totalAmount)
+    ).to.be.true;
+
+    const claimant = claimants[0];
+    await provider.connection.requestAirdrop(claimant.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    const [claimStatus] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("merkle_claim"),
+        merkleDistribution.toBuffer(),
+        claimant.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      claimant.publicKey
+    );
+    const claimAccounts = {
+      recipient: claimant.publicKey,
+      mint: mint.publicKey,
+      tokenData,
+      allocationBudget,
+      merkleDistribution,
+      claimStatus,
+      recipientTokenAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+
+    // A proof for a different amount is rejected
+    try {
+      await program.methods
+        .claimMerkleAllocation(amount.muln(2), [[...leaves[1]], [...right]])
+        .accountsPartial(claimAccounts)
+        .signers([claimant])
+        .rpc();
+      expect.fail("Expected InvalidMerkleProof");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidMerkleProof");
+    }
+
+    await program.methods
+      .claimMerkleAllocation(amount, [[...leaves[1]], [...right]])
+      .accountsPartial(claimAccounts)
+      .signers([claimant])
+      .rpc();
+
+    // The first claim records the proven amount and pays the 15% TGE unlock
+    const claimStatusAccount = await program.account.merkleClaimStatus.fetch(claimStatus);
+    const expectedTgeAmount = amount.muln(1500).divn(10000);
+    expect(claimStatusAccount.amount.eq(amount)).to.be.true;
+    expect(claimStatusAccount.claimedAmount.gte(expectedTgeAmount)).to.be.true;
+
+    const balance = await getAccount(provider.connection, recipientTokenAccount);
+    expect(balance.amount.toString()).to.equal(claimStatusAccount.claimedAmount.toString());
+
+    const distributionAccount = await program.account.merkleDistribution.fetch(
+      merkleDistribution
+    );
+    expect(distributionAccount.claimedAmount.eq(claimStatusAccount.claimedAmount)).to.be.true;
+  });
+
+  it("Initialize staking pool", async () => {
+    console.log("\n=== Testing Staking Pool Initialization ===");
+
+    const apyPercentage = 1500; // 15% APY (from tokenomics: 5-15% APY)
+    const minStakeDuration = new anchor.BN(30 * 24 * 3600); // 30 days in seconds
+
+    const tx = await program.methods
+      .initializeStaking(apyPercentage, minStakeDuration)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        stakingPool,
+        stakingVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    console.log("Staking initialization transaction:", tx);
+
+    // Verify staking pool
+    const stakingPoolAccount = await program.account.stakingPool.fetch(
+      stakingPool
+    );
+    console.log("Staking Pool:", {
+      authority: stakingPoolAccount.authority.toString(),
+      apyPercentage: stakingPoolAccount.apyPercentage,
+      minStakeDuration: stakingPoolAccount.minStakeDuration.toString(),
+      totalStaked: stakingPoolAccount.totalStaked.toString(),
+    });
+
+    expect(stakingPoolAccount.apyPercentage).to.equal(apyPercentage);
+    expect(
+      stakingPoolAccount.minStakeDuration.eq(new anchor.BN(minStakeDuration))
+    ).to.be.true;
+    expect(stakingPoolAccount.totalStaked.eq(new anchor.BN(0))).to.be.true;
+    expect(stakingPoolAccount.authority.equals(authority.publicKey)).to.be.true;
+    expect(stakingPoolAccount.mint.equals(mint.publicKey)).to.be.true;
+
+    // Pools mint rewards from the supply, so only the token authority can open one
+    const outsider = Keypair.generate();
+    await provider.connection.requestAirdrop(outsider.publicKey, LAMPORTS_PER_SOL);
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+    try {
+      await program.methods
+        .initializeStaking(65535, new anchor.BN(0))
+        .accountsPartial({
+          authority: outsider.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          stakingPool: PublicKey.findProgramAddressSync(
+            [Buffer.from("staking_pool"), outsider.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          stakingVault: PublicKey.findProgramAddressSync(
+            [Buffer.from("staking_vault"), outsider.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([outsider])
+        .rpc();
+      expect.fail("Expected ConstraintHasOne");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("ConstraintHasOne");
+    }
+
+    // Longer locks earn boosted rewards; tiers must ascend by duration
+    const day = 24 * 3600;
+    try {
+      await program.methods
+        .setStakingTiers([
+          { minDuration: new anchor.BN(180 * day), boostBps: 15000 },
+          { minDuration: new anchor.BN(30 * day), boostBps: 10000 },
+        ])
+        .accountsPartial({ authority: authority.publicKey, stakingPool })
+        .signers([authority])
+        .rpc();
+      expect.fail("Unsorted staking tiers were accepted");
+    } catch (err) {
+      expect(err.toString()).to.include("InvalidStakingTiers");
+    }
+
+    await program.methods
+      .setStakingTiers([
+        { minDuration: new anchor.BN(30 * day), boostBps: 10000 },
+        { minDuration: new anchor.BN(180 * day), boostBps: 15000 },
+      ])
+      .accountsPartial({ authority: authority.publicKey, stakingPool })
+      .signers([authority])
+      .rpc();
+
+    const tieredPool = await program.account.stakingPool.fetch(stakingPool);
+    expect(tieredPool.tiers.length).to.equal(2);
+    expect(tieredPool.tiers[1].boostBps).to.equal(15000);
+
+    // Verify staking vault exists
+    const vault = await getAccount(provider.connection, stakingVault);
+    expect(vault.amount.toString()).to.equal("0");
+    expect(vault.mint.equals(mint.publicKey)).to.be.true;
+  });
+
+  it("Stake tokens", async () => {
+    console.log("\n=== Testing Token Staking ===");
+
+    const user = Keypair.generate();
+    const stakeAmount = new anchor.BN(100_000 * 10 ** 9); // 100K tokens
+    const stakeDuration = new anchor.BN(90 * 24 * 3600); // 90 days (minimum is 30 days)
+
+    // Airdrop SOL to user
+    await provider.connection.requestAirdrop(
+      user.publicKey,
+      2 * LAMPORTS_PER_SOL
+    );
+    await new Promise((resolve) => setTimeout(resolve, 1000));
+
+    // First, mint some tokens to user using Marketing allocation (20% TGE) for instant unlock
+    const userAllocation = await findNextAllocation();
+
+    const userTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      user.publicKey
+    );
+
+    // Mint tokens to user
+    await program.methods
+      .mintTokens(stakeAmount.mul(new anchor.BN(5)), { marketing: {} }, false, []) // Mint 5x stake amount
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: user.publicKey,
+        recipientTokenAccount: userTokenAccount,
+        allocationRegistry: findAllocationRegistry(user.publicKey),
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        allocation: userAllocation,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    console.log("Tokens minted to user for staking");
+
+    // Verify user has tokens
+    const userBalance = await getAccount(provider.connection, userTokenAccount);
+    console.log("User balance before staking:", userBalance.amount.toString());
+
+    // Open two positions with different locks; each is keyed by pool, user and a per-user nonce
+    const [positionIndex] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stake_position_index"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const findStakeAccount = (nonce: number): PublicKey =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("stake_account"),
+          stakingPool.toBuffer(),
+          user.publicKey.toBuffer(),
+          new anchor.BN(nonce).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const positionAmount = stakeAmount.divn(2);
+    const durations = [stakeDuration, stakeDuration.muln(2)];
+    const stakeAccounts = durations.map((_, nonce) => findStakeAccount(nonce));
+    for (const [nonce, duration] of durations.entries()) {
+      const tx = await program.methods
+        .stakeTokens(positionAmount, duration)
+        .accountsPartial({
+          user: user.publicKey,
+          mint: mint.publicKey,
+          stakingPool,
+          userTokenAccount,
+          stakingVault,
+          positionIndex,
+          stakeAccount: stakeAccounts[nonce],
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .signers([user])
+        .rpc();
+
+      console.log("Staking transaction:", tx);
+    }
+
+    // Verify stake accounts
+    for (const [nonce, stakeAccount] of stakeAccounts.entries()) {
+      const stakeAccountData = await program.account.stakeAccount.fetch(
+        stakeAccount
+      );
+      console.log("Stake Account:", {
+        user: stakeAccountData.user.toString(),
+        amount: stakeAccountData.amount.toString(),
+        stakeTime: stakeAccountData.stakeTime.toString(),
+        unlockTime: stakeAccountData.unlockTime.toString(),
+      });
+
+      expect(stakeAccountData.amount.eq(positionAmount)).to.be.true;
+      expect(stakeAccountData.user.equals(user.publicKey)).to.be.true;
+      expect(stakeAccountData.pool.equals(stakingPool)).to.be.true;
+      expect(stakeAccountData.nonce.toNumber()).to.equal(nonce);
+      expect(
+        stakeAccountData.unlockTime
+          .sub(stakeAccountData.stakeTime)
+          .eq(durations[nonce])
+      ).to.be.true;
+    }
+
+    // The 90-day position earns 1x; the 180-day one gets the 1.5x tier
+    const boostedPositions = await Promise.all(
+      stakeAccounts.map((stakeAccount) =>
+        program.account.stakeAccount.fetch(stakeAccount)
+      )
+    );
+    expect(boostedPositions[0].boostBps).to.equal(10000);
+    expect(boostedPositions[0].weight.eq(positionAmount)).to.be.true;
+    expect(boostedPositions[1].boostBps).to.equal(15000);
+    expect(
+      boostedPositions[1].weight.eq(positionAmount.muln(3).divn(2))
+    ).to.be.true;
+
+    // The wallet can enumerate its open positions
+    const positionIndexData = await program.account.stakePositionIndex.fetch(
+      positionIndex
+    );
+    expect(positionIndexData.positions.length).to.equal(2);
+    expect(positionIndexData.positions[1].equals(stakeAccounts[1])).to.be.true;
+    expect(positionIndexData.nextNonce.toNumber()).to.equal(2);
+
+    // Verify staking pool updated
+    const stakingPoolAccount = await program.account.stakingPool.fetch(
+      stakingPool
+    );
+    expect(stakingPoolAccount.totalStaked.eq(stakeAmount)).to.be.true;
+    expect(
+      stakingPoolAccount.totalWeight.eq(
+        boostedPositions[0].weight.add(boostedPositions[1].weight)
+      )
+    ).to.be.true;
+
+    // Verify tokens moved to staking vault
+    const vaultBalance = await getAccount(provider.connection, stakingVault);
+    expect(vaultBalance.amount.toString()).to.equal(stakeAmount.toString());
+
+    // Rewards can be claimed while the principal stays locked
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    const balanceBeforeClaim = await getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    await program.methods
+      .claimStakingRewards()
+      .accountsPartial({
+        user: user.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        stakingPool,
+        userTokenAccount,
+        stakeAccount: stakeAccounts[0],
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    const claimedPosition = await program.account.stakeAccount.fetch(
+      stakeAccounts[0]
+    );
+    const balanceAfterClaim = await getAccount(
+      provider.connection,
+      userTokenAccount
+    );
+    console.log("Claimed staking rewards:", claimedPosition.claimedRewards.toString());
+    expect(claimedPosition.claimedRewards.gtn(0)).to.be.true;
+    expect(claimedPosition.lastClaimTime.gt(claimedPosition.stakeTime)).to.be.true;
+    expect(claimedPosition.amount.eq(positionAmount)).to.be.true;
+    expect(
+      (balanceAfterClaim.amount - balanceBeforeClaim.amount).toString()
+    ).to.equal(claimedPosition.claimedRewards.toString());
+    const vaultAfterClaim = await getAccount(provider.connection, stakingVault);
+    expect(vaultAfterClaim.amount.toString()).to.equal(stakeAmount.toString());
+
+    // Keepers can only compound positions that opted in
+    const compoundAccounts = {
+      caller: authority.publicKey,
+      mint: mint.publicKey,
+      tokenData,
+      stakingPool,
+      stakingVault,
+      stakeAccount: stakeAccounts[1],
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+    try {
+      await program.methods
+        .compound()
+        .accountsPartial(compoundAccounts)
+        .signers([authority])
+        .rpc();
+      expect.fail("Keeper compounded a position without auto-compound");
+    } catch (err) {
+      expect(err.toString()).to.include("AutoCompoundDisabled");
+    }
+
+    await program.methods
+      .setAutoCompound(true)
+      .accountsPartial({
+        user: user.publicKey,
+        stakeAccount: stakeAccounts[1],
+      })
+      .signers([user])
+      .rpc();
+
+    await program.methods
+      .compound()
+      .accountsPartial(compoundAccounts)
+      .signers([authority])
+      .rpc();
+
+    const compoundedPosition = await program.account.stakeAccount.fetch(
+      stakeAccounts[1]
+    );
+    const compoundedPool = await program.account.stakingPool.fetch(stakingPool);
+    const compoundedRewards = compoundedPosition.amount.sub(positionAmount);
+    console.log("Compounded staking rewards:", compoundedRewards.toString());
+    expect(compoundedPosition.autoCompound).to.be.true;
+    expect(compoundedRewards.gtn(0)).to.be.true;
+    expect(
+      compoundedPool.totalStaked.eq(stakeAmount.add(compoundedRewards))
+    ).to.be.true;
+    const vaultAfterCompound = await getAccount(
+      provider.connection,
+      stakingVault
+    );
+    expect(vaultAfterCompound.amount.toString()).to.equal(
+      compoundedPool.totalStaked.toString()
+    );
+  });
+
+  it("Update staking APY", async () => {
+    console.log("\n=== Testing Staking APY Update ===");
+
+    const before = await program.account.stakingPool.fetch(stakingPool);
+
+    await program.methods
+      .updateStakingApy(1000)
+      .accountsPartial({
+        authority: authority.publicKey,
+        stakingPool,
+      })
+      .signers([authority])
+      .rpc();
+
+    // The old rate is settled into the accumulator before the new one applies
+    const after = await program.account.stakingPool.fetch(stakingPool);
+    expect(after.apyPercentage).to.equal(1000);
+    expect(after.lastUpdateTime.gte(before.lastUpdateTime)).to.be.true;
+    expect(after.accRewardPerShare.gte(before.accRewardPerShare)).to.be.true;
+
+    // Restore the default rate for the remaining tests
+    await program.methods
+      .updateStakingApy(1500)
+      .accountsPartial({
+        authority: authority.publicKey,
+        stakingPool,
+      })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Burn tokens", async () => {
+    console.log("\n=== Testing Token Burning ===");
+
+    const burnAmount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+
+    // First, mint tokens to authority for burning using Marketing allocation (20% TGE)
+    const authorityAllocation = await findNextAllocation();
+
+    const authorityTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      authority.publicKey
+    );
+
+    // Mint tokens for burning using Marketing allocation
+    await program.methods
+      .mintTokens(burnAmount.mul(new anchor.BN(5)), { marketing: {} }, false, []) // Mint 5x burn amount
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: authority.publicKey,
+        recipientTokenAccount: authorityTokenAccount,
+        allocationRegistry: findAllocationRegistry(authority.publicKey),
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        allocation: authorityAllocation,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    console.log("Tokens minted for burning");
+
+    // Check balance before burning
+    const balanceBefore = await getAccount(
+      provider.connection,
+      authorityTokenAccount
+    );
+    console.log("Balance before burn:", balanceBefore.amount.toString());
+
+    // Get token data before burning
+    const tokenDataBefore = await program.account.tokenData.fetch(tokenData);
+    console.log(
+      "Before burn - Burned supply:",
+      tokenDataBefore.burnedSupply.toString()
+    );
+    console.log(
+      "Before burn - Circulating supply:",
+      tokenDataBefore.circulatingSupply.toString()
+    );
+
+    // Burn tokens
+    const tx = await program.methods
+      .burnTokens(burnAmount)
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        fromTokenAccount: authorityTokenAccount,
+        burnVault,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    console.log("Burn transaction:", tx);
+
+    // Verify burn
+    const tokenDataAfter = await program.account.tokenData.fetch(tokenData);
+    console.log(
+      "After burn - Burned supply:",
+      tokenDataAfter.burnedSupply.toString()
+    );
+    console.log(
+      "After burn - Circulating supply:",
+      tokenDataAfter.circulatingSupply.toString()
+    );
+
+    expect(tokenDataAfter.burnedSupply.eq(burnAmount)).to.be.true;
+    expect(
+      tokenDataAfter.circulatingSupply.eq(
+        tokenDataBefore.circulatingSupply.sub(burnAmount)
+      )
+    ).to.be.true;
+
+    // Verify tokens are in burn vault
+    const burnVaultBalance = await getAccount(provider.connection, burnVault);
+    expect(burnVaultBalance.amount.toString()).to.equal(burnAmount.toString());
+  });
+
+  it("Crank vested tokens to a cold wallet", async () => {
+    console.log("\n=== Testing Crank Claims ===");
+
+    const coldWallet = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      coldWallet.publicKey
+    );
+    const crankerTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      authority.publicKey
+    );
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: coldWallet.publicKey,
+        recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(coldWallet.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    // Tip crankers 1 token per claim from a 100 token budget; tips need a crank interval
+    const tipAmount = new anchor.BN(10 ** 9);
+    try {
+      await program.methods
+        .configureCrankTip(tipAmount, tipAmount.mul(new anchor.BN(100)), new anchor.BN(0))
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+        })
+        .rpc();
+      expect.fail("Expected InvalidCrankInterval");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("InvalidCrankInterval");
+    }
+
+    // At most one crank per 5 seconds
+    await program.methods
+      .configureCrankTip(tipAmount, tipAmount.mul(new anchor.BN(100)), new anchor.BN(5))
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+      })
+      .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    const allocationBefore = await program.account.tokenAllocation.fetch(
+      allocation
+    );
+    const crankerBefore = await getAccount(
+      provider.connection,
+      crankerTokenAccount
+    );
+
+    const crankAccounts = {
+      cranker: authority.publicKey,
+      mint: mint.publicKey,
+      tokenData,
+      allocationBudget,
+      allocation,
+      recipient: coldWallet.publicKey,
+      recipientTokenAccount,
+      allocationVault: null,
+      crankerTokenAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+
+    // The cold wallet never signs
+    await program.methods.crankClaim().accountsPartial(crankAccounts).rpc();
+
+    const allocationAfter = await program.account.tokenAllocation.fetch(
+      allocation
+    );
+    expect(allocationAfter.claimedAmount.gt(allocationBefore.claimedAmount)).to
+      .be.true;
+
+    const recipientBalance = await getAccount(
+      provider.connection,
+      recipientTokenAccount
+    );
+    expect(recipientBalance.amount.toString()).to.equal(
+      allocationAfter.claimedAmount.toString()
+    );
+
+    // The claim is logged on the allocation for reporting tools, after the TGE unlock
+    expect(allocationAfter.claimCount.toNumber()).to.equal(2);
+    const [tgeRecord, claimRecord] = allocationAfter.claimHistory;
+    expect(tgeRecord.amount.eq(allocationBefore.claimedAmount)).to.be.true;
+    expect(claimRecord.destination.equals(recipientTokenAccount)).to.be.true;
+    expect(
+      claimRecord.amount.eq(
+        allocationAfter.claimedAmount.sub(allocationBefore.claimedAmount)
+      )
+    ).to.be.true;
+
+    const crankerAfter = await getAccount(
+      provider.connection,
+      crankerTokenAccount
+    );
+    expect((crankerAfter.amount - crankerBefore.amount).toString()).to.equal(
+      tipAmount.toString()
+    );
+
+    // Cranking again straight away would only release dust, so it waits out the interval
+    try {
+      await program.methods.crankClaim().accountsPartial(crankAccounts).rpc();
+      expect.fail("Expected CrankCooldown");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("CrankCooldown");
+    }
+
+    // A compliance hold blocks claims while vesting keeps accruing
+    const setFrozen = (frozen: boolean) =>
+      program.methods
+        .setAllocationFrozen(frozen)
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocation,
+        })
+        .rpc();
+
+    await new Promise((resolve) => setTimeout(resolve, 5000));
+    await setFrozen(true);
+    try {
+      await program.methods.crankClaim().accountsPartial(crankAccounts).rpc();
+      expect.fail("Expected AllocationFrozen");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("AllocationFrozen");
+    }
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+    await setFrozen(false);
+
+    // Unfreezing releases what vested during the hold
+    await program.methods.crankClaim().accountsPartial(crankAccounts).rpc();
+    const allocationUnfrozen = await program.account.tokenAllocation.fetch(
+      allocation
+    );
+    expect(allocationUnfrozen.claimedAmount.gt(allocationAfter.claimedAmount)).to
+      .be.true;
+
+    // Opted-out recipients can only be claimed for by themselves
+    await program.methods
+      .setCrankOptOut(true)
+      .accountsPartial({
+        recipient: coldWallet.publicKey,
+        mint: mint.publicKey,
+        allocation,
+      })
+      .signers([coldWallet])
+      .rpc();
+
+    try {
+      await program.methods.crankClaim().accountsPartial(crankAccounts).rpc();
+      expect.fail("Expected CrankOptedOut");
+    } catch (err) {
+      expect(err.error.errorCode.code).to.equal("CrankOptedOut");
+    }
+  });
+
+  it("Keep the latest claims in the claim history", async () => {
+    console.log("\n=== Testing Claim History ===");
+
+    const holder = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      holder.publicKey
+    );
+
+    await program.methods
+      .mintTokens(amount, { marketing: {} }, false, [])
+      .accountsPartial({
+        authority: authority.publicKey,
+        mint: mint.publicKey,
+        tokenData,
+        allocationBudget,
+        recipient: holder.publicKey,
+        recipientTokenAccount,
+        allocationRegistry: findAllocationRegistry(holder.publicKey),
+        allocation,
+        vestingTemplate: findVestingTemplate(MARKETING),
+        allocationVault: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+      })
+      .rpc();
+
+    // Each claim waits a second so linear vesting has released something new
+    const claim = async () => {
+      await new Promise((resolve) => setTimeout(resolve, 1100));
+      await program.methods
+        .claimVestedTokens()
+        .accountsPartial({
+          recipient: holder.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation,
+          allocationVault: null,
+          positionTokenAccount: null,
+          recipientTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([holder])
+        .rpc();
+    };
+
+    // The TGE unlock takes the first slot, so 15 claims fill the history
+    for (let i = 0; i < 15; i++) {
+      await claim();
+    }
+    const fullHistory = (await program.account.tokenAllocation.fetch(allocation))
+      .claimHistory;
+    expect(fullHistory.length).to.equal(16);
+
+    // Two more claims wrap around and overwrite the TGE entry and the first claim
+    await claim();
+    await claim();
+    const allocationAccount = await program.account.tokenAllocation.fetch(allocation);
+    expect(allocationAccount.claimCount.toNumber()).to.equal(18);
+    expect(allocationAccount.claimHistory.length).to.equal(16);
+
+    const latestFullTimestamp = fullHistory[15].timestamp;
+    expect(allocationAccount.claimHistory[0].timestamp.gt(latestFullTimestamp)).to.be.true;
+    expect(
+      allocationAccount.claimHistory[1].timestamp.gt(allocationAccount.claimHistory[0].timestamp)
+    ).to.be.true;
+    for (let slot = 2; slot < 16; slot++) {
+      expect(allocationAccount.claimHistory[slot].timestamp.eq(fullHistory[slot].timestamp)).to
+        .be.true;
+      expect(allocationAccount.claimHistory[slot].amount.eq(fullHistory[slot].amount)).to.be
+        .true;
+    }
+  });
+
+  it("Emit an event for every claim", async () => {
+    console.log("\n=== Testing Claim Events ===");
+
+    const holder = Keypair.generate();
+    const amount = new anchor.BN(1_000_000 * 10 ** 9); // 1M tokens
+    const allocation = await findNextAllocation();
+    const recipientTokenAccount = await getAssociatedTokenAddress(
+      mint.publicKey,
+      holder.publicKey
+    );
+
+    const events: anchor.IdlEvents<VtrToken>["tokensClaimed"][] = [];
+    const listener = program.addEventListener("tokensClaimed", (event) => {
+      if (event.allocation.equals(allocation)) {
+        events.push(event);
+      }
+    });
+
+    try {
+      // The TGE unlock paid at creation is the first claim
+      await program.methods
+        .mintTokens(amount, { marketing: {} }, false, [])
+        .accountsPartial({
+          authority: authority.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          recipient: holder.publicKey,
+          recipientTokenAccount,
+          allocationRegistry: findAllocationRegistry(holder.publicKey),
+          allocation,
+          vestingTemplate: findVestingTemplate(MARKETING),
+          allocationVault: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+
+      await new Promise((resolve) => setTimeout(resolve, 1100));
+      await program.methods
+        .claimVestedTokens()
+        .accountsPartial({
+          recipient: holder.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocation,
+          allocationVault: null,
+          positionTokenAccount: null,
+          recipientTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([holder])
+        .rpc();
+
+      await new Promise((resolve) => setTimeout(resolve, 1100));
+      await program.methods
+        .claimAllVestedTokens()
+        .accountsPartial({
+          recipient: holder.publicKey,
+          mint: mint.publicKey,
+          tokenData,
+          allocationBudget,
+          allocationRegistry: findAllocationRegistry(holder.publicKey),
+          recipientTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts([{ pubkey: allocation, isWritable: true, isSigner: false }])
+        .signers([holder])
+        .rpc();
+
+      // Logs reach the listener asynchronously
+      for (let attempt = 0; attempt < 20 && events.length < 3; attempt++) {
+        await new Promise((resolve) => setTimeout(resolve, 250));
+      }
+    } finally {
+      await program.removeEventListener(listener);
+    }
+
+    // Each event matches the claim logged on the allocation
+    const { claimHistory } = await program.account.tokenAllocation.fetch(allocation);
+    expect(events.length).to.equal(3);
+    expect(claimHistory.length).to.equal(3);
+    expect(claimHistory[0].amount.eq(amount.muln(2000).divn(10000))).to.be.true;
+    events.forEach((event, index) => {
+      expect(event.recipient.equals(holder.publicKey)).to.be.true;
+      expect(event.destination.equals(recipientTokenAccount)).to.be.true;
+      expect(event.amount.eq(claimHistory[index].amount)).to.be.true;
+      expect(event.timestamp.eq(claimHistory[index].timestamp)).to.be.true;
+    });
+  });
+
+  it("Display final token statistics", async () => {
     console.log("\n=== Final Token Statistics ===");
 
     const tokenDataAccount = await program.account.tokenData.fetch(tokenData);
@@ -563,15 +2704,23 @@ describe("vtr-token", () => {
       tokenDataAccount.circulatingSupply.toString()
     );
     console.log("- Burned Supply:", tokenDataAccount.burnedSupply.toString());
+    console.log("- Locked Supply:", tokenDataAccount.lockedSupply.toString());
+    console.log("- Allocated Supply:", tokenDataAccount.allocatedSupply.toString());
     console.log("- Mint Supply:", mintAccount.supply.toString());
 
     console.log("\nStaking Pool:");
     console.log("- Total Staked:", stakingPoolAccount.totalStaked.toString());
     console.log("- APY:", stakingPoolAccount.apyPercentage / 100, "%");
 
-    // Verify circulating supply equals mint supply
+    // Commitments never exceed the total supply
+    expect(tokenDataAccount.allocatedSupply.lte(tokenDataAccount.totalSupply)).to
+      .be.true;
+
+    // Verify mint supply is the unlocked plus the escrowed supply
     expect(mintAccount.supply.toString()).to.equal(
-      tokenDataAccount.circulatingSupply.toString()
+      tokenDataAccount.circulatingSupply
+        .add(tokenDataAccount.lockedSupply)
+        .toString()
     );
 
     // Verify tokenomics compliance